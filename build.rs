@@ -38,13 +38,16 @@ fn main() {
     // `memory.x` is changed.
     println!("cargo:rerun-if-changed=memory.x");
 
-    println!("cargo:rustc-link-arg-bins=--nmagic");
-    println!("cargo:rustc-link-arg-bins=-Tlink.x");
+    // Host builds, which are only for running the tests, link normally
+    if env::var("CARGO_CFG_TARGET_OS").as_deref() == Ok("none") {
+        println!("cargo:rustc-link-arg-bins=--nmagic");
+        println!("cargo:rustc-link-arg-bins=-Tlink.x");
+    }
 
     let mut ci_tag = "unknown".to_string();
 
     if let Ok(output) = std::process::Command::new("git")
-        .args(&[
+        .args([
             "-c",
             "core.abbrev=8",
             "show",
@@ -1,5 +1,5 @@
 use crate::fixed_str::FixedString;
-use crate::screen::SCREEN;
+use crate::screen::{Rotation, SCREEN};
 use embassy_rp::flash::{
     Async, ERASE_SIZE, Error as FlashError, Flash as RpFlash, PAGE_SIZE, WRITE_SIZE,
 };
@@ -231,6 +231,15 @@ pub async fn config_command(args: &[&str]) {
                 }
                 return;
             }
+            if *key == "rotate" {
+                let mut config = CONFIG.get().lock().await;
+                match config.fetch(key).await {
+                    Ok(Some(val)) => print!("{val}\r\n"),
+                    Ok(None) => print!("0\r\n"),
+                    Err(e) => print!("{e:?}\r\n"),
+                }
+                return;
+            }
             let mut config = CONFIG.get().lock().await;
             let value = config.fetch(key).await;
             print!("{value:?}\r\n");
@@ -239,6 +248,9 @@ pub async fn config_command(args: &[&str]) {
             if *key == "scroll" {
                 SCREEN.get().lock().await.set_max_scrollback(200);
             }
+            if *key == "rotate" {
+                SCREEN.get().lock().await.set_rotation(Rotation::Deg0);
+            }
             let mut config = CONFIG.get().lock().await;
             let result = config.remove(key).await;
             print!("{result:?}\r\n");
@@ -257,6 +269,15 @@ pub async fn config_command(args: &[&str]) {
                     return;
                 }
             }
+            if *key == "rotate" {
+                match value.parse::<u32>().ok().and_then(Rotation::from_degrees) {
+                    Some(rotation) => SCREEN.get().lock().await.set_rotation(rotation),
+                    None => {
+                        print!("rotate value must be 0, 90, 180 or 270\r\n");
+                        return;
+                    }
+                }
+            }
             let value: StrValue = match (*value).try_into() {
                 Ok(v) => v,
                 Err(err) => {
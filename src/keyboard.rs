@@ -1,5 +1,5 @@
 use crate::process::current_proc;
-use crate::screen::SCREEN;
+use crate::screen::{self, CopyMotion, SCREEN, ScreenModel};
 use core::fmt::Formatter;
 use core::sync::atomic::{AtomicU8, Ordering};
 use embassy_rp::i2c::I2c;
@@ -290,7 +290,7 @@ pub async fn keyboard_reader(
     I2C.get().lock().await.replace(i2c_bus);
 
     // Enable LCD backlight
-    set_lcd_backlight(0x80).await;
+    screen::set_brightness(screen::DEFAULT_BRIGHTNESS).await;
 
     let mut keyboard = KeyBoardState::default();
 
@@ -329,6 +329,14 @@ pub async fn keyboard_reader(
         if let Some(key) = keyboard.process().await {
             log::info!("key == {key:?}");
             if key.state == KeyState::Pressed {
+                {
+                    let mut screen = SCREEN.get().lock().await;
+                    if screen.in_copy_mode() {
+                        copy_mode_key_input(&mut screen, key);
+                        continue;
+                    }
+                }
+
                 match key.key {
                     Key::F5 if key.modifiers == Modifiers::CTRL => {
                         reboot_bootsel();
@@ -337,13 +345,29 @@ pub async fn keyboard_reader(
                         reboot();
                     }
                     Key::F2 if key.modifiers == Modifiers::CTRL => {
-                        set_lcd_backlight(0x20).await;
+                        screen::set_brightness(0x20).await;
                     }
                     Key::F3 if key.modifiers == Modifiers::CTRL => {
-                        set_lcd_backlight(0x80).await;
+                        screen::set_brightness(screen::DEFAULT_BRIGHTNESS).await;
                     }
                     Key::F4 if key.modifiers == Modifiers::CTRL => {
-                        set_lcd_backlight(0xff).await;
+                        screen::set_brightness(0xff).await;
+                    }
+                    Key::F6 if key.modifiers == Modifiers::CTRL => {
+                        screen::brightness_down().await;
+                    }
+                    Key::F7 if key.modifiers == Modifiers::CTRL => {
+                        screen::brightness_up().await;
+                    }
+                    Key::F8 if key.modifiers == Modifiers::CTRL => {
+                        let mut screen = SCREEN.get().lock().await;
+                        let inverted = screen.inverted();
+                        screen.set_inverted(!inverted);
+                    }
+                    Key::F9 if key.modifiers == Modifiers::CTRL => {
+                        let mut screen = SCREEN.get().lock().await;
+                        let scroll_lock = screen.scroll_lock();
+                        screen.set_scroll_lock(!scroll_lock);
                     }
                     Key::Char('=') if key.modifiers == Modifiers::CTRL => {
                         SCREEN.get().lock().await.increase_font();
@@ -351,12 +375,29 @@ pub async fn keyboard_reader(
                     Key::Char('-') if key.modifiers == Modifiers::CTRL => {
                         SCREEN.get().lock().await.decrease_font();
                     }
+                    Key::Char('X') if key.modifiers.contains(Modifiers::CTRL) => {
+                        SCREEN.get().lock().await.enter_copy_mode();
+                    }
                     Key::Up if key.modifiers == Modifiers::CTRL => {
                         SCREEN.get().lock().await.scroll_view_up(1);
                     }
                     Key::Down if key.modifiers == Modifiers::CTRL => {
                         SCREEN.get().lock().await.scroll_view_down(1);
                     }
+                    Key::PageUp
+                        if key
+                            .modifiers
+                            .intersects(Modifiers::LSHIFT | Modifiers::RSHIFT) =>
+                    {
+                        SCREEN.get().lock().await.scroll_view_page_up();
+                    }
+                    Key::PageDown
+                        if key
+                            .modifiers
+                            .intersects(Modifiers::LSHIFT | Modifiers::RSHIFT) =>
+                    {
+                        SCREEN.get().lock().await.scroll_view_page_down();
+                    }
                     _ => {
                         let proc = current_proc();
                         if let Err(_) = with_timeout(Duration::from_millis(100), async {
@@ -374,6 +415,29 @@ pub async fn keyboard_reader(
     }
 }
 
+/// Handle a key press while copy mode is active, using vi-style bindings
+fn copy_mode_key_input(screen: &mut ScreenModel, key: KeyReport) {
+    match key.key {
+        Key::Left | Key::Char('h') => screen.copy_mode_move(CopyMotion::Left),
+        Key::Right | Key::Char('l') => screen.copy_mode_move(CopyMotion::Right),
+        Key::Up | Key::Char('k') => screen.copy_mode_move(CopyMotion::Up),
+        Key::Down | Key::Char('j') => screen.copy_mode_move(CopyMotion::Down),
+        Key::Char('w') => screen.copy_mode_move(CopyMotion::WordForward),
+        Key::Char('b') => screen.copy_mode_move(CopyMotion::WordBackward),
+        Key::Home | Key::Char('0') => screen.copy_mode_move(CopyMotion::LineStart),
+        Key::End | Key::Char('$') => screen.copy_mode_move(CopyMotion::LineEnd),
+        Key::Char('n') => screen.copy_mode_select_link(true),
+        Key::Char('N') => screen.copy_mode_select_link(false),
+        Key::Char('v') if key.modifiers.contains(Modifiers::CTRL) => {
+            screen.copy_mode_toggle_block()
+        }
+        Key::Char('v') => screen.copy_mode_toggle_selection(),
+        Key::Enter | Key::Char('y') => screen.exit_copy_mode(true),
+        Key::Escape | Key::Char('q') => screen.exit_copy_mode(false),
+        _ => {}
+    }
+}
+
 pub struct BatteryStatus(u8);
 
 impl BatteryStatus {
@@ -440,7 +504,7 @@ pub async fn backlight_command(args: &[&str]) {
                 set_keyboard_backlight(value).await;
             }
             "lcd" => {
-                set_lcd_backlight(value).await;
+                screen::set_brightness(value).await;
             }
             _ => {
                 print!("Invalid arguments {args:?}\r\n");
@@ -1,33 +1,40 @@
-#![feature(impl_trait_in_assoc_type)]
-#![no_std]
-#![no_main]
-
-use crate::config::{CONFIG, Flash};
-use crate::heap::{HEAP, init_qmi_psram_heap};
-use crate::psram::{init_psram, init_psram_qmi};
-use crate::screen::SCREEN;
-use crate::storage::init_storage;
-use core::cell::RefCell;
-use core::fmt::Write as _;
-use embassy_embedded_hal::shared_bus::blocking::spi::SpiDeviceWithConfig;
-use embassy_executor::Spawner;
-use embassy_rp::block::ImageDef;
-use embassy_rp::gpio::{Level, Output};
-use embassy_rp::peripherals::{PIO0, PIO1, SPI1, TRNG, UART0, UART1, USB};
-use embassy_rp::spi::Spi;
-use embassy_rp::uart::BufferedInterruptHandler;
-use embassy_rp::watchdog::Watchdog;
-use embassy_rp::{bind_interrupts, spi, usb};
-use embassy_sync::blocking_mutex::Mutex;
-use embassy_sync::blocking_mutex::raw::NoopRawMutex;
-use embassy_time::{Delay, Duration, Ticker, Timer};
-use mipidsi::Builder;
-use mipidsi::interface::SpiInterface;
-use mipidsi::models::ILI9488Rgb565;
-use mipidsi::options::{ColorInversion, ColorOrder, Orientation};
-use panic_persist as _;
-use static_cell::StaticCell;
-
+#![cfg_attr(target_os = "none", feature(impl_trait_in_assoc_type))]
+#![cfg_attr(target_os = "none", no_std)]
+#![cfg_attr(target_os = "none", no_main)]
+// Built for the host, just the terminal emulation is compiled so that
+// its tests can run, leaving much of it unused
+#![cfg_attr(not(target_os = "none"), allow(dead_code))]
+
+#[cfg(target_os = "none")]
+use {
+    crate::config::{CONFIG, Flash},
+    crate::heap::{HEAP, init_qmi_psram_heap},
+    crate::psram::{init_psram, init_psram_qmi},
+    crate::screen::{Rotation, SCREEN},
+    crate::storage::init_storage,
+    core::cell::RefCell,
+    core::fmt::Write as _,
+    embassy_embedded_hal::shared_bus::blocking::spi::SpiDeviceWithConfig,
+    embassy_executor::Spawner,
+    embassy_rp::block::ImageDef,
+    embassy_rp::gpio::{Level, Output},
+    embassy_rp::peripherals::{PIO0, PIO1, SPI1, TRNG, UART0, UART1, USB},
+    embassy_rp::spi::Spi,
+    embassy_rp::uart::BufferedInterruptHandler,
+    embassy_rp::watchdog::Watchdog,
+    embassy_rp::{bind_interrupts, spi, usb},
+    embassy_sync::blocking_mutex::Mutex,
+    embassy_sync::blocking_mutex::raw::NoopRawMutex,
+    embassy_time::{Delay, Duration, Ticker, Timer},
+    mipidsi::Builder,
+    mipidsi::interface::SpiInterface,
+    mipidsi::models::ILI9488Rgb565,
+    mipidsi::options::{ColorInversion, ColorOrder, Orientation},
+    panic_persist as _,
+    static_cell::StaticCell,
+};
+
+#[cfg(target_os = "none")]
 macro_rules! print {
     ($($args:tt)+) => {
         {
@@ -48,27 +55,42 @@ macro_rules! print {
     }
 }
 
+#[cfg(target_os = "none")]
 use crate::screen::PicoCalcDisplay;
 
+#[cfg(target_os = "none")]
 mod config;
+#[cfg(target_os = "none")]
 mod fixed_str;
+#[cfg(target_os = "none")]
 mod heap;
+#[cfg(target_os = "none")]
 mod keyboard;
+#[cfg(target_os = "none")]
 mod logging;
+#[cfg(target_os = "none")]
 mod net;
+#[cfg(target_os = "none")]
 mod process;
+#[cfg(target_os = "none")]
 mod psram;
+#[cfg(target_os = "none")]
 mod rng;
 mod screen;
+#[cfg(target_os = "none")]
 mod storage;
+#[cfg(target_os = "none")]
 mod time;
 
+#[cfg(target_os = "none")]
 const MAX_SPI_FREQ: u32 = 62_500_000;
 
+#[cfg(target_os = "none")]
 #[unsafe(link_section = ".start_block")]
 #[used]
 pub static IMAGE_DEF: ImageDef = ImageDef::secure_exe();
 
+#[cfg(target_os = "none")]
 #[unsafe(link_section = ".bi_entries")]
 #[used]
 pub static PICOTOOL_ENTRIES: [embassy_rp::binary_info::EntryAddr; 4] = [
@@ -82,6 +104,7 @@ pub static PICOTOOL_ENTRIES: [embassy_rp::binary_info::EntryAddr; 4] = [
     embassy_rp::binary_info::rp_program_build_attribute!(),
 ];
 
+#[cfg(target_os = "none")]
 bind_interrupts!(struct Irqs {
     USBCTRL_IRQ => usb::InterruptHandler<USB>;
     PIO0_IRQ_0 => embassy_rp::pio::InterruptHandler<PIO0>;
@@ -92,6 +115,7 @@ bind_interrupts!(struct Irqs {
     TRNG_IRQ => embassy_rp::trng::InterruptHandler<TRNG>;
 });
 
+#[cfg(target_os = "none")]
 #[embassy_executor::task]
 async fn watchdog_task(mut watchdog: Watchdog) {
     if let Some(reason) = watchdog.reset_reason() {
@@ -107,6 +131,7 @@ async fn watchdog_task(mut watchdog: Watchdog) {
     }
 }
 
+#[cfg(target_os = "none")]
 /// Returns the amount of RAM available to use as stack space.
 /// This gives a sense of the amount of free memory in the system.
 /// It is not a directly useful metric.
@@ -125,6 +150,11 @@ fn get_max_usable_stack() -> usize {
     start_ptr - 0x20000000 /* where RAM starts in memory.x */
 }
 
+/// Built for the host, there is nothing to run but the tests
+#[cfg(not(target_os = "none"))]
+fn main() {}
+
+#[cfg(target_os = "none")]
 #[embassy_executor::main]
 async fn main(spawner: Spawner) {
     let p = embassy_rp::init(Default::default());
@@ -270,6 +300,14 @@ async fn main(spawner: Spawner) {
         }
     }
 
+    // Load screen rotation config
+    if let Ok(Some(val_str)) = CONFIG.get().lock().await.fetch("rotate").await {
+        let rotation = val_str.parse::<u32>().ok().and_then(Rotation::from_degrees);
+        if let Some(rotation) = rotation {
+            crate::screen::SCREEN.get().lock().await.set_rotation(rotation);
+        }
+    }
+
     crate::net::setup_wifi(
         &spawner, p.PIN_23, p.PIN_24, p.PIN_25, p.PIN_29, p.PIO0, p.DMA_CH0,
     )
@@ -298,6 +336,7 @@ macro_rules! static_bytes {
     };
 }
 
+#[cfg(target_os = "none")]
 pub fn byte_size<V: humansize::ToF64 + humansize::Unsigned>(
     n: V,
 ) -> humansize::SizeFormatter<V, humansize::FormatSizeOptions> {
@@ -4,7 +4,7 @@ use crate::keyboard::{Key, KeyReport, KeyState, Modifiers};
 use crate::net::alloc::string::ToString;
 use crate::process::{LineEditor, Process, assign_proc, assign_proc_if};
 use crate::rng::PicoRng;
-use crate::screen::{SCREEN, SCREEN_HEIGHT, SCREEN_WIDTH, Screen};
+use crate::screen::{SCREEN, Screen};
 use alloc::boxed::Box;
 use alloc::string::String;
 use alloc::sync::Arc;
@@ -146,6 +146,8 @@ const TIMEOUT_DURATION: Duration = Duration::from_secs(10);
 
 async fn ssh_channel_task(mut channel: ChanInOut<'_, '_>, key_rx: Arc<Channel<CS, KeyReport, 4>>) {
     log::info!("ssh_channel_task waiting for output");
+    // Replies to queries made before this session aren't ours to send
+    SCREEN.get().lock().await.take_replies();
 
     loop {
         let mut buf = [0u8; 1024];
@@ -160,7 +162,17 @@ async fn ssh_channel_task(mut channel: ChanInOut<'_, '_>, key_rx: Arc<Channel<CS
                         log::warn!("ssh_channel_task: EOF on ssh channel");
                         return;
                     }
-                    SCREEN.get().lock().await.parse_bytes(&buf[0..n]);
+                    let replies = {
+                        let mut screen = SCREEN.get().lock().await;
+                        screen.parse_bytes(&buf[0..n]);
+                        screen.take_replies()
+                    };
+                    if !replies.is_empty() {
+                        log::info!(
+                            "{:?}",
+                            with_timeout(TIMEOUT_DURATION, channel.write_all(&replies)).await
+                        );
+                    }
                 }
                 Err(err) => {
                     print!("\u{1b}[1mssh_channel_task: {err:?}\r\n");
@@ -214,11 +226,18 @@ async fn ssh_channel_task(mut channel: ChanInOut<'_, '_>, key_rx: Arc<Channel<CS
                         .await
                     );
                 } else {
+                    let app_cursor_keys = SCREEN.get().lock().await.app_cursor_keys();
                     let text = match key_report.key {
                         Key::Enter => "\r",
                         Key::BackSpace => "\u{7f}",
                         Key::Tab => "\t",
                         Key::Escape => "\u{1b}",
+                        Key::Up if app_cursor_keys => "\u{1b}OA",
+                        Key::Down if app_cursor_keys => "\u{1b}OB",
+                        Key::Right if app_cursor_keys => "\u{1b}OC",
+                        Key::Left if app_cursor_keys => "\u{1b}OD",
+                        Key::Home if app_cursor_keys => "\u{1b}OH",
+                        Key::End if app_cursor_keys => "\u{1b}OF",
                         Key::Up => "\u{1b}[A",
                         Key::Down => "\u{1b}[B",
                         Key::Right => "\u{1b}[C",
@@ -373,13 +392,14 @@ async fn ssh_session_task(host: String, command: Option<String>) {
                                             let screen = SCREEN.get().lock().await;
                                             let rows = screen.height();
                                             let cols = screen.width();
+                                            let (width, height) = screen.screen_size();
 
                                             sunset::Pty {
                                                 term,
                                                 rows: rows.into(),
                                                 cols: cols.into(),
-                                                width: SCREEN_WIDTH as u32,
-                                                height: SCREEN_HEIGHT as u32,
+                                                width,
+                                                height,
                                                 modes: Vec::new(),
                                             }
                                         };
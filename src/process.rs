@@ -3,11 +3,14 @@ use crate::keyboard::{Key, KeyReport, KeyState};
 use crate::screen::Screen;
 use crate::storage::ls_command;
 use alloc::boxed::Box;
+use alloc::collections::BTreeMap;
 use alloc::string::String;
 use alloc::sync::Arc;
 use alloc::vec::Vec;
 use core::cell::RefCell;
 use core::fmt::Write;
+use core::future::Future;
+use core::pin::Pin;
 use embassy_sync::blocking_mutex::CriticalSectionMutex;
 use embassy_sync::blocking_mutex::raw::CriticalSectionRawMutex;
 use embassy_sync::lazy_lock::LazyLock;
@@ -51,6 +54,58 @@ pub fn current_proc() -> ProcHandle {
     CURRENT.get().lock(|cell| Arc::clone(&*cell.borrow()))
 }
 
+pub type CommandFuture<'a> = Pin<Box<dyn Future<Output = ()> + 'a>>;
+
+/// A built-in shell command. It is passed the words of the command
+/// line, the first being the command's own name.
+pub type CommandHandler = for<'a> fn(&'a [&'a str]) -> CommandFuture<'a>;
+
+static COMMANDS: LazyLock<CriticalSectionMutex<RefCell<BTreeMap<&'static str, CommandHandler>>>> =
+    LazyLock::new(|| CriticalSectionMutex::new(RefCell::new(builtin_commands())));
+
+fn builtin_commands() -> BTreeMap<&'static str, CommandHandler> {
+    let mut commands: BTreeMap<&'static str, CommandHandler> = BTreeMap::new();
+    commands.insert("cls", |argv| Box::pin(crate::screen::cls_command(argv)));
+    commands.insert("echo", |argv| Box::pin(crate::screen::echo_command(argv)));
+    commands.insert("reset", |argv| Box::pin(crate::screen::reset_command(argv)));
+    commands.insert("bat", |argv| Box::pin(crate::keyboard::battery_command(argv)));
+    commands.insert("bl", |argv| Box::pin(crate::keyboard::backlight_command(argv)));
+    commands.insert("bootsel", |_| Box::pin(async { crate::keyboard::reboot_bootsel() }));
+    commands.insert("config", |argv| Box::pin(crate::config::config_command(argv)));
+    commands.insert("free", |argv| Box::pin(crate::heap::free_command(argv)));
+    commands.insert("ls", |argv| Box::pin(ls_command(argv)));
+    commands.insert("reboot", |_| Box::pin(async { crate::keyboard::reboot() }));
+    commands.insert("ssh", |argv| Box::pin(crate::net::ssh_command(argv)));
+    commands.insert("time", |argv| Box::pin(crate::time::time_command(argv)));
+    commands
+}
+
+/// Adds a command to the local shell, replacing any of the same name
+pub fn register_command(name: &'static str, handler: CommandHandler) {
+    COMMANDS
+        .get()
+        .lock(|commands| commands.borrow_mut().insert(name, handler));
+}
+
+/// Runs the command named by the first word of `line`, passing it
+/// all of the words. Returns false if there is no such command.
+pub async fn run_command(line: &str) -> bool {
+    let argv: Vec<&str> = line.split_whitespace().collect();
+    let Some(name) = argv.first() else {
+        return true;
+    };
+    let handler = COMMANDS
+        .get()
+        .lock(|commands| commands.borrow().get(name).copied());
+    match handler {
+        Some(handler) => {
+            handler(&argv).await;
+            true
+        }
+        None => false,
+    }
+}
+
 #[async_trait::async_trait(?Send)]
 pub trait Process {
     async fn key_input(&self, key: KeyReport);
@@ -116,23 +171,10 @@ impl LocalShell {
     }
 
     async fn dispatch_command(&self, command: &str) {
-        let argv: Vec<&str> = command.split(' ').collect();
-        let arg0 = argv[0];
-        match arg0 {
-            "bat" => crate::keyboard::battery_command(&argv).await,
-            "bl" => crate::keyboard::backlight_command(&argv).await,
-            "bootsel" => crate::keyboard::reboot_bootsel(),
-            "cls" => crate::screen::cls_command(&argv).await,
-            "config" => crate::config::config_command(&argv).await,
-            "free" => crate::heap::free_command(&argv).await,
-            "ls" => ls_command(&argv).await,
-            "reboot" => crate::keyboard::reboot(),
-            "ssh" => crate::net::ssh_command(&argv).await,
-            "time" => crate::time::time_command(&argv).await,
-            _ => {
-                let mut screen = SCREEN.get().lock().await;
-                write!(screen, "Unknown command: {arg0}\r\n").ok();
-            }
+        if !run_command(command).await {
+            let arg0 = command.split_whitespace().next().unwrap_or_default();
+            let mut screen = SCREEN.get().lock().await;
+            write!(screen, "Unknown command: {arg0}\r\n").ok();
         }
     }
 }
@@ -1,9 +1,11 @@
 use core::fmt;
+use core::sync::atomic::{AtomicU32, AtomicU8, Ordering};
 use core::ops::{Deref, DerefMut};
-use embassy_sync::blocking_mutex::raw::{CriticalSectionRawMutex, NoopRawMutex};
+use embassy_sync::blocking_mutex::raw::CriticalSectionRawMutex;
 use embassy_sync::lazy_lock::LazyLock;
 use embassy_sync::mutex::Mutex as AsyncMutex;
-use embassy_time::{Duration, Ticker};
+use embassy_sync::signal::Signal;
+use embassy_time::Duration;
 use embedded_graphics::mono_font::{MonoFont, MonoTextStyleBuilder};
 use embedded_graphics::pixelcolor::{Rgb565, Rgb888};
 use embedded_graphics::prelude::*;
@@ -11,20 +13,31 @@ use embedded_graphics::primitives::*;
 use embedded_graphics::primitives::PrimitiveStyle;
 use embedded_graphics::Pixel;
 use embedded_graphics::text::Text;
-use embassy_embedded_hal::shared_bus::blocking::spi::SpiDeviceWithConfig;
-use embassy_rp::gpio::Output;
-use embassy_rp::peripherals::SPI1;
-use mipidsi::interface::SpiInterface;
-use mipidsi::models::ILI9488Rgb565;
+use unicode_width::UnicodeWidthChar;
+#[cfg(target_os = "none")]
+use {
+    embassy_embedded_hal::shared_bus::blocking::spi::SpiDeviceWithConfig,
+    embassy_rp::gpio::Output,
+    embassy_rp::peripherals::SPI1,
+    embassy_sync::blocking_mutex::raw::NoopRawMutex,
+    embassy_time::{with_deadline, Instant, Timer},
+    mipidsi::interface::SpiInterface,
+    mipidsi::models::{ILI9488Rgb565, Model},
+};
 
 extern crate alloc;
+use alloc::string::String;
+use alloc::collections::VecDeque;
 use alloc::vec::Vec;
 use alloc::vec;
 
+/// Size of the PicoCalc's panel, which the screen is laid out on
+/// unless `ScreenModel::set_panel_size` says otherwise
 pub const SCREEN_HEIGHT: u16 = 320;
 pub const SCREEN_WIDTH: u16 = 320;
 
 // Define PicoCalcDisplay here so it can be used in main.rs and here
+#[cfg(target_os = "none")]
 pub type PicoCalcDisplay<'a> = mipidsi::Display<
     SpiInterface<
         'a,
@@ -40,6 +53,22 @@ pub type PicoCalcDisplay<'a> = mipidsi::Display<
     Output<'a>,
 >;
 
+/// What `update_display` draws to: the panel, or a stand-in such as
+/// embedded-graphics' `MockDisplay` when rendering off the device
+pub trait TerminalDisplay: DrawTarget<Color = Rgb565> {
+    /// Shows frame memory from row `offset` at the top of the
+    /// panel. Stand-ins without hardware scrolling can ignore it as
+    /// long as they present frame memory the same way.
+    fn set_scroll_offset(&mut self, offset: u16);
+}
+
+#[cfg(target_os = "none")]
+impl TerminalDisplay for PicoCalcDisplay<'_> {
+    fn set_scroll_offset(&mut self, offset: u16) {
+        self.set_vertical_scroll_offset(offset).ok();
+    }
+}
+
 static FONTS: &[&MonoFont] = &[
     &profont::PROFONT_7_POINT,
     &profont::PROFONT_9_POINT,
@@ -50,9 +79,114 @@ static FONTS: &[&MonoFont] = &[
     &profont::PROFONT_24_POINT,
 ];
 
+/// Index into FONTS used when the screen is first created
+const DEFAULT_FONT: usize = 2;
+
+/// Distance between the default tab stops
+const TAB_WIDTH: usize = 8;
+
+/// Bounds on how often `screen_painter` redraws. Below the lower
+/// bound a frame can take longer to send to the panel than the
+/// interval; above the upper bound typing feels laggy.
+const MIN_REFRESH_INTERVAL: Duration = Duration::from_millis(20);
+const MAX_REFRESH_INTERVAL: Duration = Duration::from_millis(1000);
+
+/// Shortest time in milliseconds between redraws by `screen_painter`
+static REFRESH_INTERVAL_MS: AtomicU32 = AtomicU32::new(200);
+
+/// Sets how often the screen may be redrawn, clamped to 20ms..=1s.
+/// Shorter intervals make output appear sooner at the cost of power.
+pub fn set_refresh_interval(interval: Duration) {
+    let interval = interval.clamp(MIN_REFRESH_INTERVAL, MAX_REFRESH_INTERVAL);
+    REFRESH_INTERVAL_MS.store(interval.as_millis() as u32, Ordering::Relaxed);
+}
+
+pub fn refresh_interval() -> Duration {
+    Duration::from_millis(REFRESH_INTERVAL_MS.load(Ordering::Relaxed) as u64)
+}
+
+/// LCD backlight level set at startup
+pub const DEFAULT_BRIGHTNESS: u8 = 0x80;
+
+/// How much `brightness_up` and `brightness_down` change the level
+const BRIGHTNESS_STEP: u8 = 0x20;
+
+/// LCD backlight level last set, from 0 (off) to 255
+static BRIGHTNESS: AtomicU8 = AtomicU8::new(DEFAULT_BRIGHTNESS);
+
+/// Sets the LCD backlight level, from 0 (off) to 255. The backlight
+/// is dimmed by PWM from the keyboard controller, which is handed
+/// the I2C bus in `keyboard_reader`, so the level is sent to it.
+#[cfg(target_os = "none")]
+pub async fn set_brightness(level: u8) {
+    BRIGHTNESS.store(level, Ordering::Relaxed);
+    crate::keyboard::set_lcd_backlight(level).await;
+}
+
+pub fn brightness() -> u8 {
+    BRIGHTNESS.load(Ordering::Relaxed)
+}
+
+/// Raises the backlight a step, stopping at full brightness
+#[cfg(target_os = "none")]
+pub async fn brightness_up() {
+    set_brightness(brightness().saturating_add(BRIGHTNESS_STEP)).await;
+}
+
+/// Lowers the backlight a step, stopping short of turning it off
+#[cfg(target_os = "none")]
+pub async fn brightness_down() {
+    set_brightness(brightness().saturating_sub(BRIGHTNESS_STEP).max(1)).await;
+}
+
+/// How long blinking text stays shown or hidden
+const BLINK_INTERVAL: Duration = Duration::from_millis(500);
+
+/// Width in pixels of the scrollbar shown while viewing scrollback
+const SCROLLBAR_WIDTH: u32 = 3;
+
+/// Largest gap in pixels that `set_line_spacing` allows between rows
+const MAX_LINE_SPACING: u32 = 8;
+
+/// Column count that DECCOLM (`CSI ? 3 h`) asks for
+const WIDE_COLUMNS: usize = 132;
+
+/// Widest panel that `set_panel_size` allows, which still fits
+/// `WIDE_COLUMNS` in a font bigger than the smallest
+const MAX_PANEL_WIDTH: u16 = 800;
+
+/// Most cells of a line that fit across the widest panel, in the
+/// smallest font
+const MAX_DRAWN_CELLS: usize = MAX_PANEL_WIDTH as usize
+    / (profont::PROFONT_7_POINT.character_size.width + profont::PROFONT_7_POINT.character_spacing) as usize;
+
+fn default_tab_stops(cols: usize) -> Vec<bool> {
+    (0..cols).map(|x| x > 0 && x % TAB_WIDTH == 0).collect()
+}
+
+/// Returns how many of a grid's `cols` fit on a line of `size`
+fn line_cols(cols: usize, size: LineSize) -> usize {
+    match size {
+        LineSize::Single => cols,
+        _ => (cols / 2).max(1),
+    }
+}
+
+/// Returns the (cols, rows) grid that `font` yields on a screen of
+/// `size` pixels with `line_spacing` pixels between rows
+fn grid_size(font: &MonoFont, line_spacing: u32, size: (u32, u32)) -> (usize, usize) {
+    let cols = (size.0 / (font.character_size.width + font.character_spacing)) as usize;
+    let rows = (size.1 / (font.character_size.height + line_spacing)) as usize;
+    // Even a font too big for the panel gets a cell to draw in
+    (cols.max(1), rows.max(1))
+}
+
 pub static SCREEN: LazyLock<AsyncMutex<CriticalSectionRawMutex, Screen>> =
     LazyLock::new(|| AsyncMutex::new(Screen::new()));
 
+/// Raised whenever the screen may have changed, to wake `screen_painter`
+static REPAINT: Signal<CriticalSectionRawMutex, ()> = Signal::new();
+
 pub struct Screen {
     model: ScreenModel,
     parser: vte::Parser,
@@ -67,6 +201,7 @@ impl Deref for Screen {
 
 impl DerefMut for Screen {
     fn deref_mut(&mut self) -> &mut ScreenModel {
+        REPAINT.signal(());
         &mut self.model
     }
 }
@@ -80,6 +215,7 @@ impl Screen {
     }
 
     pub fn parse_bytes(&mut self, bytes: &[u8]) {
+        REPAINT.signal(());
         for byte in bytes {
             self.parser.advance(&mut self.model, *byte);
         }
@@ -90,6 +226,7 @@ impl Screen {
     }
     
     pub fn clear(&mut self) {
+        REPAINT.signal(());
         self.model.clear();
     }
 }
@@ -125,90 +262,474 @@ pub enum Color {
     Indexed(u8),
 }
 
+/// Rgb565 from 8-bit channels, usable in consts
+const fn rgb(r: u8, g: u8, b: u8) -> Rgb565 {
+    Rgb565::new(r >> 3, g >> 2, b >> 3)
+}
+
+/// Parses an X color spec as sent in OSC color escapes, either
+/// `rgb:R/G/B` with 1 to 4 hex digits per channel or `#RRGGBB`
+fn parse_color_spec(spec: &[u8]) -> Option<Rgb565> {
+    let spec = core::str::from_utf8(spec).ok()?;
+    if let Some(channels) = spec.strip_prefix("rgb:") {
+        let mut channels = channels.split('/').map(scale_hex_channel);
+        let (r, g, b) = (channels.next()??, channels.next()??, channels.next()??);
+        if channels.next().is_some() {
+            return None;
+        }
+        Some(rgb(r, g, b))
+    } else if let Some(hex) = spec.strip_prefix('#') {
+        if hex.len() != 6 {
+            return None;
+        }
+        let channel = |i: usize| scale_hex_channel(hex.get(i..i + 2)?);
+        Some(rgb(channel(0)?, channel(2)?, channel(4)?))
+    } else {
+        None
+    }
+}
+
+/// Scales a channel of 1 to 4 hex digits to 8 bits
+fn scale_hex_channel(digits: &str) -> Option<u8> {
+    if digits.is_empty() || digits.len() > 4 || !digits.bytes().all(|b| b.is_ascii_hexdigit()) {
+        return None;
+    }
+    let value = u32::from_str_radix(digits, 16).ok()?;
+    let max = (1 << (4 * digits.len())) - 1;
+    Some((value * 255 / max) as u8)
+}
+
+/// The colors that programs pick by name or by an index below 16,
+/// along with the defaults used for unstyled text
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct Palette {
+    /// The 8 standard colors followed by their bright variants
+    pub ansi: [Rgb565; 16],
+    pub fg: Rgb565,
+    pub bg: Rgb565,
+    pub cursor: Rgb565,
+}
+
+impl Palette {
+    /// Plain primaries, with xterm's bright colors
+    pub const DEFAULT: Palette = Palette {
+        ansi: [
+            Rgb565::BLACK,
+            Rgb565::RED,
+            Rgb565::GREEN,
+            Rgb565::YELLOW,
+            Rgb565::BLUE,
+            Rgb565::MAGENTA,
+            Rgb565::CYAN,
+            Rgb565::CSS_LIGHT_GRAY,
+            rgb(0x7f, 0x7f, 0x7f),
+            rgb(0xff, 0x00, 0x00),
+            rgb(0x00, 0xff, 0x00),
+            rgb(0xff, 0xff, 0x00),
+            rgb(0x5c, 0x5c, 0xff),
+            rgb(0xff, 0x00, 0xff),
+            rgb(0x00, 0xff, 0xff),
+            rgb(0xff, 0xff, 0xff),
+        ],
+        fg: Rgb565::CSS_LIGHT_GRAY,
+        bg: Rgb565::BLACK,
+        cursor: Rgb565::WHITE,
+    };
+
+    /// Solarized dark
+    pub const SOLARIZED: Palette = Palette {
+        ansi: [
+            rgb(0x07, 0x36, 0x42),
+            rgb(0xdc, 0x32, 0x2f),
+            rgb(0x85, 0x99, 0x00),
+            rgb(0xb5, 0x89, 0x00),
+            rgb(0x26, 0x8b, 0xd2),
+            rgb(0xd3, 0x36, 0x82),
+            rgb(0x2a, 0xa1, 0x98),
+            rgb(0xee, 0xe8, 0xd5),
+            rgb(0x00, 0x2b, 0x36),
+            rgb(0xcb, 0x4b, 0x16),
+            rgb(0x58, 0x6e, 0x75),
+            rgb(0x65, 0x7b, 0x83),
+            rgb(0x83, 0x94, 0x96),
+            rgb(0x6c, 0x71, 0xc4),
+            rgb(0x93, 0xa1, 0xa1),
+            rgb(0xfd, 0xf6, 0xe3),
+        ],
+        fg: rgb(0x83, 0x94, 0x96),
+        bg: rgb(0x00, 0x2b, 0x36),
+        cursor: rgb(0x93, 0xa1, 0xa1),
+    };
+}
+
+/// Corrects 24-bit colors on their way to the panel, whose 5 and 6
+/// bit channels otherwise lose most of the dark tones. The same
+/// table is applied to each channel.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct ColorCorrection {
+    lut: [u8; 256],
+}
+
+impl Default for ColorCorrection {
+    fn default() -> Self {
+        Self::IDENTITY
+    }
+}
+
+impl ColorCorrection {
+    /// Colors pass through unchanged
+    pub const IDENTITY: ColorCorrection = ColorCorrection::new(1.0, 1.0);
+
+    /// Builds the table for `gamma`, where values below 1 lift the
+    /// dark tones, and then scales by `brightness`
+    pub const fn new(gamma: f64, brightness: f64) -> Self {
+        let mut lut = [0u8; 256];
+        let mut i = 1;
+        while i < 256 {
+            let level = brightness * 255.0 * pow_unit(i as f64 / 255.0, gamma) + 0.5;
+            lut[i] = if level >= 255.0 { 255 } else if level <= 0.0 { 0 } else { level as u8 };
+            i += 1;
+        }
+        Self { lut }
+    }
+
+    fn apply(&self, r: u8, g: u8, b: u8) -> Rgb565 {
+        let lut = &self.lut;
+        Rgb888::new(lut[r as usize], lut[g as usize], lut[b as usize]).into()
+    }
+}
+
+/// `x` to the power of `y` for `x` in (0, 1], as exp(y ln x), in a
+/// form that can run at compile time without libm
+const fn pow_unit(x: f64, y: f64) -> f64 {
+    // ln x = k ln 2 + ln m, with m in [0.5, 1]
+    let mut m = x;
+    let mut k = 0.0;
+    while m < 0.5 {
+        m *= 2.0;
+        k += 1.0;
+    }
+    // ln m = 2 atanh(z), which converges quickly for small z
+    let z = (m - 1.0) / (m + 1.0);
+    let mut ln_m = 0.0;
+    let mut term = z;
+    let mut n = 1.0;
+    while n < 30.0 {
+        ln_m += term / n;
+        term *= z * z;
+        n += 2.0;
+    }
+    let ln_x = 2.0 * ln_m - k * core::f64::consts::LN_2;
+
+    // exp(t) = exp(t / 2^8)^(2^8), with a short series for the
+    // small exponent
+    let t = y * ln_x / 256.0;
+    let mut e = 1.0 + t * (1.0 + t / 2.0 * (1.0 + t / 3.0 * (1.0 + t / 4.0)));
+    let mut squarings = 0;
+    while squarings < 8 {
+        e *= e;
+        squarings += 1;
+    }
+    e
+}
+
 impl Color {
-    fn to_rgb565(self, is_bg: bool) -> Rgb565 {
+    fn to_rgb565(self, palette: &Palette, correction: &ColorCorrection) -> Rgb565 {
         match self {
-            Color::Black => Rgb565::BLACK,
-            Color::Red => Rgb565::RED,
-            Color::Green => Rgb565::GREEN,
-            Color::Yellow => Rgb565::YELLOW,
-            Color::Blue => Rgb565::BLUE,
-            Color::Magenta => Rgb565::MAGENTA,
-            Color::Cyan => Rgb565::CYAN,
-            Color::White => Rgb565::WHITE,
-            Color::BrightBlack => Rgb565::new(10, 20, 10), // Approx
-            Color::BrightRed => Rgb565::new(31, 20, 20),
-            Color::BrightGreen => Rgb565::new(20, 63, 20),
-            Color::BrightYellow => Rgb565::new(31, 63, 20),
-            Color::BrightBlue => Rgb565::new(20, 20, 31),
-            Color::BrightMagenta => Rgb565::new(31, 20, 31),
-            Color::BrightCyan => Rgb565::new(20, 63, 31),
-            Color::BrightWhite => Rgb565::WHITE,
-            Color::DefaultFg => Rgb565::CSS_LIGHT_GRAY,
-            Color::DefaultBg => Rgb565::BLACK,
-            Color::Rgb(r, g, b) => Rgb888::new(r, g, b).into(),
+            Color::Black => palette.ansi[0],
+            Color::Red => palette.ansi[1],
+            Color::Green => palette.ansi[2],
+            Color::Yellow => palette.ansi[3],
+            Color::Blue => palette.ansi[4],
+            Color::Magenta => palette.ansi[5],
+            Color::Cyan => palette.ansi[6],
+            Color::White => palette.ansi[7],
+            Color::BrightBlack => palette.ansi[8],
+            Color::BrightRed => palette.ansi[9],
+            Color::BrightGreen => palette.ansi[10],
+            Color::BrightYellow => palette.ansi[11],
+            Color::BrightBlue => palette.ansi[12],
+            Color::BrightMagenta => palette.ansi[13],
+            Color::BrightCyan => palette.ansi[14],
+            Color::BrightWhite => palette.ansi[15],
+            Color::DefaultFg => palette.fg,
+            Color::DefaultBg => palette.bg,
+            Color::Rgb(r, g, b) => correction.apply(r, g, b),
             Color::Indexed(i) => {
-                // Simple mapping for first 16 colors, else default
-                if i < 8 {
-                    // map to standard colors
-                    match i {
-                        0 => Rgb565::BLACK,
-                        1 => Rgb565::RED,
-                        2 => Rgb565::GREEN,
-                        3 => Rgb565::YELLOW,
-                        4 => Rgb565::BLUE,
-                        5 => Rgb565::MAGENTA,
-                        6 => Rgb565::CYAN,
-                        7 => Rgb565::CSS_LIGHT_GRAY,
-                        _ => Rgb565::WHITE,
-                    }
-                } else if i < 16 {
-                    // brights
-                     match i {
-                        8 => Rgb565::new(10, 20, 10),
-                        9 => Rgb565::new(31, 20, 20),
-                        10 => Rgb565::new(20, 63, 20),
-                        11 => Rgb565::new(31, 63, 20),
-                        12 => Rgb565::new(20, 20, 31),
-                        13 => Rgb565::new(31, 20, 31),
-                        14 => Rgb565::new(20, 63, 31),
-                        15 => Rgb565::WHITE,
-                        _ => Rgb565::WHITE,
-                    }
+                if i < 16 {
+                    palette.ansi[i as usize]
+                } else if i < 232 {
+                    // 6x6x6 color cube
+                    const LEVELS: [u8; 6] = [0, 95, 135, 175, 215, 255];
+                    let i = i - 16;
+                    let r = LEVELS[(i / 36) as usize];
+                    let g = LEVELS[((i / 6) % 6) as usize];
+                    let b = LEVELS[(i % 6) as usize];
+                    correction.apply(r, g, b)
                 } else {
-                    if is_bg { Rgb565::BLACK } else { Rgb565::WHITE }
+                    // 24 step grayscale ramp
+                    let level = 8 + (i - 232) * 10;
+                    correction.apply(level, level, level)
                 }
             }
         }
     }
 }
 
+/// Parse the extended color that follows SGR 38, 48 or 58.
+/// It is either packed into the colon separated subparams of `param`
+/// (`38:2::r:g:b`, or `38:2:r:g:b` as some programs emit it, and
+/// `38:5:n`), or spread over the semicolon separated params that
+/// follow it, in which case those params are consumed from `iter`.
+fn parse_sgr_color<'a>(
+    param: &[u16],
+    iter: &mut impl Iterator<Item = &'a [u16]>,
+) -> Option<Color> {
+    if param.len() > 1 {
+        match param[1] {
+            2 => {
+                let rgb = if param.len() >= 6 {
+                    &param[3..6]
+                } else {
+                    param.get(2..5)?
+                };
+                Some(Color::Rgb(rgb[0] as u8, rgb[1] as u8, rgb[2] as u8))
+            }
+            5 => Some(Color::Indexed(*param.get(2)? as u8)),
+            _ => None,
+        }
+    } else {
+        match iter.next()?[0] {
+            2 => {
+                let mut next = || iter.next().map(|p| p[0] as u8);
+                Some(Color::Rgb(next()?, next()?, next()?))
+            }
+            5 => Some(Color::Indexed(iter.next()?[0] as u8)),
+            _ => None,
+        }
+    }
+}
+
+/// The 16 named colors, in palette order
+const NAMED_COLORS: [Color; 16] = [
+    Color::Black,
+    Color::Red,
+    Color::Green,
+    Color::Yellow,
+    Color::Blue,
+    Color::Magenta,
+    Color::Cyan,
+    Color::White,
+    Color::BrightBlack,
+    Color::BrightRed,
+    Color::BrightGreen,
+    Color::BrightYellow,
+    Color::BrightBlue,
+    Color::BrightMagenta,
+    Color::BrightCyan,
+    Color::BrightWhite,
+];
+
+/// A `Color` in four bytes: the kind in the top byte and the palette
+/// index or RGB channels in the rest
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+struct PackedColor(u32);
+
+impl PackedColor {
+    const NAMED: u32 = 0;
+    const DEFAULT_FG: u32 = 1;
+    const DEFAULT_BG: u32 = 2;
+    const INDEXED: u32 = 3;
+    const RGB: u32 = 4;
+
+    fn new(color: Color) -> Self {
+        let (kind, value) = match color {
+            Color::DefaultFg => (Self::DEFAULT_FG, 0),
+            Color::DefaultBg => (Self::DEFAULT_BG, 0),
+            Color::Indexed(i) => (Self::INDEXED, i as u32),
+            Color::Rgb(r, g, b) => (Self::RGB, ((r as u32) << 16) | ((g as u32) << 8) | b as u32),
+            named => {
+                let index = NAMED_COLORS.iter().position(|&c| c == named).unwrap_or(0);
+                (Self::NAMED, index as u32)
+            }
+        };
+        Self((kind << 24) | value)
+    }
+
+    fn get(self) -> Color {
+        let value = self.0 & 0xff_ffff;
+        match self.0 >> 24 {
+            Self::DEFAULT_FG => Color::DefaultFg,
+            Self::DEFAULT_BG => Color::DefaultBg,
+            Self::INDEXED => Color::Indexed(value as u8),
+            Self::RGB => Color::Rgb((value >> 16) as u8, (value >> 8) as u8, value as u8),
+            _ => NAMED_COLORS[value as usize % NAMED_COLORS.len()],
+        }
+    }
+}
+
+/// How a cell is underlined, as picked by SGR 4 and its `4:n` forms
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub enum UnderlineStyle {
+    #[default]
+    None,
+    Single,
+    Double,
+    Curly,
+    Dotted,
+    Dashed,
+}
+
+bitflags::bitflags! {
+    /// The on/off attributes of a cell
+    #[derive(Clone, Copy, PartialEq, Eq, Debug, Default)]
+    struct AttrFlags: u8 {
+        const BOLD = 1;
+        const FAINT = 2;
+        const ITALIC = 4;
+        /// Set by DECSCA rather than SGR, and not drawn: selective
+        /// erases leave the cell alone
+        const PROTECTED = 8;
+        const REVERSE = 16;
+        const STRIKETHROUGH = 32;
+        const BLINK = 64;
+        const CONCEAL = 128;
+    }
+}
+
+/// Rendition of a cell. Every cell stores one, so it is kept packed.
 #[derive(Clone, Copy, PartialEq, Debug)]
 pub struct Attrs {
-    fg: Color,
-    bg: Color,
-    bold: bool,
-    underline: bool,
-    reverse: bool,
+    fg: PackedColor,
+    bg: PackedColor,
+    /// Color of the underline set by SGR 58, where `DefaultFg` means
+    /// the underline follows the text color
+    underline_color: PackedColor,
+    underline: UnderlineStyle,
+    flags: AttrFlags,
+    /// OSC 8 hyperlink, as an index into `ScreenModel::hyperlinks`
+    link: Option<u8>,
 }
 
 impl Default for Attrs {
     fn default() -> Self {
         Self {
-            fg: Color::DefaultFg,
-            bg: Color::DefaultBg,
-            bold: false,
-            underline: false,
-            reverse: false,
+            fg: PackedColor::new(Color::DefaultFg),
+            bg: PackedColor::new(Color::DefaultBg),
+            underline_color: PackedColor::new(Color::DefaultFg),
+            underline: UnderlineStyle::None,
+            flags: AttrFlags::empty(),
+            link: None,
+        }
+    }
+}
+
+impl Attrs {
+    pub fn fg(&self) -> Color {
+        self.fg.get()
+    }
+
+    pub fn bg(&self) -> Color {
+        self.bg.get()
+    }
+
+    fn set_fg(&mut self, color: Color) {
+        self.fg = PackedColor::new(color);
+    }
+
+    fn set_bg(&mut self, color: Color) {
+        self.bg = PackedColor::new(color);
+    }
+
+    /// The underline color, if it differs from the text color
+    pub fn underline_color(&self) -> Option<Color> {
+        match self.underline_color.get() {
+            Color::DefaultFg => None,
+            color => Some(color),
+        }
+    }
+
+    fn set_underline_color(&mut self, color: Option<Color>) {
+        self.underline_color = PackedColor::new(color.unwrap_or(Color::DefaultFg));
+    }
+
+    pub fn bold(&self) -> bool {
+        self.flags.contains(AttrFlags::BOLD)
+    }
+
+    pub fn faint(&self) -> bool {
+        self.flags.contains(AttrFlags::FAINT)
+    }
+
+    pub fn italic(&self) -> bool {
+        self.flags.contains(AttrFlags::ITALIC)
+    }
+
+    pub fn underline(&self) -> bool {
+        self.underline != UnderlineStyle::None
+    }
+
+    pub fn underline_style(&self) -> UnderlineStyle {
+        self.underline
+    }
+
+    /// The underline to draw, which for an otherwise plain hyperlink
+    /// is a single one
+    fn drawn_underline(&self) -> Option<UnderlineStyle> {
+        match self.underline {
+            UnderlineStyle::None if self.link.is_some() => Some(UnderlineStyle::Single),
+            UnderlineStyle::None => None,
+            style => Some(style),
         }
     }
+
+    pub fn reverse(&self) -> bool {
+        self.flags.contains(AttrFlags::REVERSE)
+    }
+
+    pub fn strikethrough(&self) -> bool {
+        self.flags.contains(AttrFlags::STRIKETHROUGH)
+    }
+
+    pub fn blink(&self) -> bool {
+        self.flags.contains(AttrFlags::BLINK)
+    }
+
+    pub fn conceal(&self) -> bool {
+        self.flags.contains(AttrFlags::CONCEAL)
+    }
+
+    /// Whether the cell is part of an OSC 8 hyperlink
+    pub fn is_link(&self) -> bool {
+        self.link.is_some()
+    }
+
+    /// Whether DECSCA protected the cell from selective erases
+    pub fn protected(&self) -> bool {
+        self.flags.contains(AttrFlags::PROTECTED)
+    }
+}
+
+/// Size of the characters on a line, set by DECDWL / DECDHL
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+enum LineSize {
+    Single,
+    DoubleWidth,
+    /// Double width and height, showing the top halves of the glyphs
+    DoubleHeightTop,
+    /// Double width and height, showing the bottom halves of the glyphs
+    DoubleHeightBottom,
 }
 
 #[derive(Clone)]
 struct ScreenLine {
     chars: Vec<char>,
     attrs: Vec<Attrs>,
-    dirty: bool,
+    size: LineSize,
+    /// Cells needing a redraw, as a half-open `(start, end)` range
+    dirty: Option<(usize, usize)>,
+    /// Half-open column spans of URLs found when the line was finalized
+    links: LineLinks,
 }
 
 impl ScreenLine {
@@ -216,55 +737,498 @@ impl ScreenLine {
         Self {
             chars: vec![' '; width],
             attrs: vec![Attrs::default(); width],
-            dirty: true,
+            size: LineSize::Single,
+            dirty: Some((0, width)),
+            links: LineLinks::new(),
         }
     }
     
     fn clear(&mut self) {
         for c in self.chars.iter_mut() { *c = ' '; }
         for a in self.attrs.iter_mut() { *a = Attrs::default(); }
-        self.dirty = true;
+        self.size = LineSize::Single;
+        self.touch_all();
+        self.links.clear();
+    }
+
+    /// Marks the cells in `start..end` for redraw
+    fn touch(&mut self, start: usize, end: usize) {
+        self.dirty = Some(match self.dirty {
+            Some((a, b)) => (a.min(start), b.max(end)),
+            None => (start, end),
+        });
+    }
+
+    fn touch_all(&mut self) {
+        self.touch(0, self.chars.len());
+    }
+
+    /// Truncate or pad the line to `width` cells
+    fn resize(&mut self, width: usize) {
+        self.chars.resize(width, ' ');
+        self.attrs.resize(width, Attrs::default());
+        // A wide character in the last cell lost its right half
+        if let Some(last) = self.chars.last_mut()
+            && last.width() == Some(2)
+        {
+            *last = ' ';
+        }
+        self.links.retain(|&(_, end)| end as usize <= width);
+        self.touch_all();
+    }
+}
+
+/// Whether to keep a copy of what each cell was last drawn as, so
+/// that cells whose appearance didn't change aren't sent to the
+/// panel again. Costs 20 bytes per cell, which is 23 KiB for the
+/// default font's 45x26 grid and 40 KiB for the smallest font's 64x32.
+const SHADOW_FRAMEBUFFER: bool = true;
+
+/// What a cell was last drawn as
+#[derive(Clone, Copy, PartialEq)]
+struct DrawnCell {
+    c: char,
+    fg: Rgb565,
+    bg: Rgb565,
+    bold: bool,
+    italic: bool,
+    /// Style and color of the underline, if there is one
+    underline: Option<(UnderlineStyle, Rgb565)>,
+    strikethrough: bool,
+    wide: bool,
+    size: LineSize,
+}
+
+/// Most distinct unsupported private modes remembered
+const MAX_UNKNOWN_MODES: usize = 8;
+
+/// Longest mouse report, an SGR one with five digit coordinates
+const MAX_MOUSE_REPORT_LEN: usize = 24;
+
+/// Most bytes of replies to the host queued between reads
+const MAX_REPLY_LEN: usize = 128;
+
+/// Stored in the cell covered by the right half of a wide character
+const WIDE_CONTINUATION: char = '\0';
+
+/// Maximum number of URLs recorded for a single line
+const MAX_LINE_LINKS: usize = 4;
+
+type LineLinks = heapless::Vec<(u16, u16), MAX_LINE_LINKS>;
+
+/// Most OSC 8 hyperlink targets remembered at once; the oldest is
+/// reused when a new one arrives, unlinking the cells left on it
+const MAX_HYPERLINKS: usize = 8;
+
+/// Longest OSC 8 hyperlink target kept, in bytes
+const MAX_HYPERLINK_LEN: usize = 128;
+
+/// Longest status bar text kept, in bytes
+const MAX_STATUS_LEN: usize = 80;
+
+/// Longest window title kept from OSC 0 / OSC 2, in bytes
+const MAX_TITLE_LEN: usize = 64;
+
+fn starts_with(chars: &[char], prefix: &str) -> bool {
+    let mut chars = chars.iter();
+    prefix.chars().all(|p| chars.next() == Some(&p))
+}
+
+fn is_url_char(c: char) -> bool {
+    c.is_ascii_graphic() && !matches!(c, '<' | '>' | '"' | '`' | '{' | '}' | '|' | '\\' | '^')
+}
+
+/// Find the http and https URLs in a line of text
+fn scan_links(chars: &[char]) -> LineLinks {
+    let mut links = LineLinks::new();
+    let mut x = 0;
+    while x < chars.len() {
+        let scheme_len = if starts_with(&chars[x..], "https://") {
+            8
+        } else if starts_with(&chars[x..], "http://") {
+            7
+        } else {
+            x += 1;
+            continue;
+        };
+
+        let body = x + scheme_len;
+        let mut end = body;
+        while end < chars.len() && is_url_char(chars[end]) {
+            end += 1;
+        }
+        // Trailing punctuation is more likely to belong to the
+        // surrounding text than to the URL
+        while end > body && matches!(chars[end - 1], '.' | ',' | ':' | ';' | '!' | '?' | ')' | ']' | '\'') {
+            end -= 1;
+        }
+        if end > body && links.push((x as u16, end as u16)).is_err() {
+            break;
+        }
+        x = end.max(body);
+    }
+    links
+}
+
+/// Cursor state captured by DECSC (ESC 7) or CSI s and
+/// restored by DECRC (ESC 8) or CSI u
+#[derive(Clone, Copy, Default)]
+struct SavedCursor {
+    cursor_x: usize,
+    cursor_y: usize,
+    attrs: Attrs,
+    charset: Charset,
+}
+
+/// A position in the combined scrollback and screen lines, where
+/// line 0 is the oldest line of scrollback.
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Debug)]
+struct AbsPos {
+    line: usize,
+    col: usize,
+}
+
+/// How the terminal cursor is drawn, as selected by DECSCUSR
+#[derive(Clone, Copy, PartialEq, Debug)]
+enum CursorShape {
+    Block,
+    Underline,
+    Bar,
+}
+
+/// Which mouse events the running program asked to be told about
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub enum MouseTracking {
+    #[default]
+    Off,
+    /// `CSI ? 1000 h`: button presses and releases
+    Normal,
+    /// `CSI ? 1002 h`: also motion while a button is held
+    ButtonEvent,
+}
+
+/// How far the screen is turned clockwise on the panel, for however
+/// the PicoCalc is held
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub enum Rotation {
+    #[default]
+    Deg0,
+    Deg90,
+    Deg180,
+    Deg270,
+}
+
+impl Rotation {
+    pub fn from_degrees(degrees: u32) -> Option<Self> {
+        match degrees {
+            0 => Some(Self::Deg0),
+            90 => Some(Self::Deg90),
+            180 => Some(Self::Deg180),
+            270 => Some(Self::Deg270),
+            _ => None,
+        }
+    }
+
+    pub fn degrees(self) -> u32 {
+        match self {
+            Self::Deg0 => 0,
+            Self::Deg90 => 90,
+            Self::Deg180 => 180,
+            Self::Deg270 => 270,
+        }
+    }
+
+    /// Whether rows of the screen run down the panel rather than
+    /// across it, which the panel's vertical scrolling can't move
+    fn is_sideways(self) -> bool {
+        matches!(self, Self::Deg90 | Self::Deg270)
+    }
+}
+
+/// Character set designated as G0 by `ESC ( F`
+#[derive(Clone, Copy, PartialEq, Debug, Default)]
+enum Charset {
+    #[default]
+    Ascii,
+    /// DEC Special Graphics, where lowercase letters draw lines
+    DecGraphics,
+}
+
+impl Charset {
+    fn translate(self, c: char) -> char {
+        if self == Charset::Ascii {
+            return c;
+        }
+        match c {
+            '_' => ' ',
+            '`' => '\u{25C6}', // ◆
+            'a' => '\u{2592}', // ▒
+            'b' => '\u{2409}', // ␉
+            'c' => '\u{240C}', // ␌
+            'd' => '\u{240D}', // ␍
+            'e' => '\u{240A}', // ␊
+            'f' => '\u{00B0}', // °
+            'g' => '\u{00B1}', // ±
+            'h' => '\u{2424}', // ␤
+            'i' => '\u{240B}', // ␋
+            'j' => '\u{2518}', // ┘
+            'k' => '\u{2510}', // ┐
+            'l' => '\u{250C}', // ┌
+            'm' => '\u{2514}', // └
+            'n' => '\u{253C}', // ┼
+            'o' => '\u{23BA}', // ⎺
+            'p' => '\u{23BB}', // ⎻
+            'q' => '\u{2500}', // ─
+            'r' => '\u{23BC}', // ⎼
+            's' => '\u{23BD}', // ⎽
+            't' => '\u{251C}', // ├
+            'u' => '\u{2524}', // ┤
+            'v' => '\u{2534}', // ┴
+            'w' => '\u{252C}', // ┬
+            'x' => '\u{2502}', // │
+            'y' => '\u{2264}', // ≤
+            'z' => '\u{2265}', // ≥
+            '{' => '\u{03C0}', // π
+            '|' => '\u{2260}', // ≠
+            '}' => '\u{00A3}', // £
+            '~' => '\u{00B7}', // ·
+            c => c,
+        }
+    }
+}
+
+/// Cursor movements available in copy mode
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum CopyMotion {
+    Left,
+    Right,
+    Up,
+    Down,
+    WordForward,
+    WordBackward,
+    LineStart,
+    LineEnd,
+}
+
+/// State for copy mode, which moves a selection cursor over the
+/// scrollback independently of the terminal cursor
+struct CopyMode {
+    cursor: AbsPos,
+    /// Where the selection was started; None until a selection is begun
+    anchor: Option<AbsPos>,
+    /// Select the rectangle between anchor and cursor rather than
+    /// the run of text between them
+    block: bool,
+}
+
+impl CopyMode {
+    /// Returns the selected range, ordered, with both ends inclusive
+    fn selection(&self) -> Option<(AbsPos, AbsPos)> {
+        let anchor = self.anchor?;
+        Some((anchor.min(self.cursor), anchor.max(self.cursor)))
+    }
+
+    /// Adjust for `n` lines having been discarded from the front of scrollback
+    fn discard_lines(&mut self, n: usize) {
+        self.cursor.line = self.cursor.line.saturating_sub(n);
+        if let Some(anchor) = &mut self.anchor {
+            anchor.line = anchor.line.saturating_sub(n);
+        }
     }
 }
 
+/// Punctuation that is treated as part of a word, in addition to alphanumerics
+const DEFAULT_WORD_CHARS: &str = "_-./";
+
 pub struct ScreenModel {
-    lines: Vec<ScreenLine>,
-    scrollback: Vec<ScreenLine>,
+    lines: VecDeque<ScreenLine>,
+    scrollback: VecDeque<ScreenLine>,
     viewport_offset: usize,
     max_scrollback: usize,
+    /// While scrolled back, output doesn't return the view to the bottom
+    scroll_lock: bool,
+    /// Output arrived while scroll lock held the view back
+    output_held: bool,
+    /// Pixels of background between rows, below each row's glyphs
+    line_spacing: u32,
     cursor_x: usize,
     cursor_y: usize,
     current_attrs: Attrs,
+    saved_cursor: SavedCursor,
+    /// Cursor saved on switching to the alternate screen, kept apart
+    /// from DECSC's so that neither overwrites the other
+    primary_cursor: SavedCursor,
+    /// The primary screen's DECSC cursor while the alternate screen,
+    /// which has its own, is shown
+    primary_saved_cursor: SavedCursor,
     font: &'static MonoFont<'static>,
+    /// The user's configured font; `font` may differ while
+    /// 132-column mode is active
+    font_index: usize,
     rows: usize,
     cols: usize,
+    /// First row of the scroll region
+    scroll_top: usize,
+    /// Last row of the scroll region, inclusive
+    scroll_bottom: usize,
+    /// Whether each column has a tab stop
+    tab_stops: Vec<bool>,
     full_repaint: bool,
+    /// Rows scrolled off the top since the last `update_display`
+    pending_scroll: usize,
+    /// Frame memory row shown at the top of the panel
+    scroll_offset: usize,
+    /// Whether the panel's hardware scroll can be used, or each
+    /// scroll has to repaint the screen
+    hardware_scroll: bool,
+    /// What each screen cell was last drawn as, row by row; `None`
+    /// where that's unknown. Empty unless `SHADOW_FRAMEBUFFER`.
+    shadow: Vec<Option<DrawnCell>>,
+    copy_mode: Option<CopyMode>,
+    clipboard: String,
+    /// Non-alphanumeric characters considered part of a word
+    word_chars: String,
+    /// Whether blinking text is currently shown
+    blink_on: bool,
+    /// Window title set by OSC 0 / OSC 2
+    title: heapless::String<MAX_TITLE_LEN>,
+    /// Whether the bottom row of the panel is kept for the status bar
+    /// rather than being part of the grid
+    status_bar: bool,
+    status_text: heapless::String<MAX_STATUS_LEN>,
+    status_dirty: bool,
+    /// Targets of OSC 8 hyperlinks, referred to by `Attrs::link`
+    hyperlinks: heapless::Vec<heapless::String<MAX_HYPERLINK_LEN>, MAX_HYPERLINKS>,
+    /// Entry of `hyperlinks` to reuse once it is full
+    next_hyperlink: usize,
+    /// The primary screen's lines while the alternate screen is active
+    primary_lines: Option<VecDeque<ScreenLine>>,
+    cursor_visible: bool,
+    cursor_shape: CursorShape,
+    /// G0 character set, which is always the one in use
+    charset: Charset,
+    /// Screen cell the cursor was last drawn over
+    prev_cursor: Option<(usize, usize)>,
+    /// DECAWM: whether printing past the last column wraps
+    autowrap: bool,
+    /// DECOM: row addressing is relative to the scroll region
+    origin_mode: bool,
+    bracketed_paste: bool,
+    /// DECCKM: cursor keys send SS3 rather than CSI sequences
+    app_cursor_keys: bool,
+    /// DECKPAM / DECKPNM: the keypad sends application sequences
+    app_keypad: bool,
+    mouse_tracking: MouseTracking,
+    /// `CSI ? 1006 h`: mouse reports use the SGR form
+    sgr_mouse: bool,
+    /// `CSI ? 1004 h`: report gaining and losing focus
+    focus_events: bool,
+    /// Unsupported private modes seen, for debugging
+    unknown_modes: heapless::Vec<u16, MAX_UNKNOWN_MODES>,
+    /// DECSCNM: the default colors are swapped across the screen
+    reverse_screen: bool,
+    /// Every color drawn is inverted, whatever the program asked for
+    inverted: bool,
+    /// The grid is drawn in the middle of the panel rather than at
+    /// its top left, sharing out the pixels left over
+    center_grid: bool,
+    rotation: Rotation,
+    /// Size of the panel in pixels, as it is mounted
+    panel_width: u16,
+    panel_height: u16,
+    /// Set after printing in the last column; the next printed
+    /// character wraps before it is drawn
+    wrap_pending: bool,
+    /// Last graphic character printed, repeated by REP
+    last_char: Option<char>,
+    /// The colors in use, which OSC 4, 10, 11 and 12 change
+    palette: Palette,
+    /// The colors set by `set_palette`, which a reset returns to
+    user_palette: Palette,
+    color_correction: ColorCorrection,
+    /// Replies to host queries, waiting to be sent back
+    replies: heapless::Vec<u8, MAX_REPLY_LEN>,
+    /// Set by BEL; the next frame is drawn inverted
+    bell: bool,
+    /// Whether the last frame drawn was an inverted bell frame
+    bell_shown: bool,
 }
 
 impl Default for ScreenModel {
     fn default() -> Self {
-        let font = FONTS[2];
-        let cols = ((SCREEN_WIDTH as u32) / (font.character_size.width + font.character_spacing)) as usize;
-        let rows = ((SCREEN_HEIGHT as u32) / font.character_size.height) as usize;
+        let font = FONTS[DEFAULT_FONT];
+        let (cols, rows) = grid_size(font, 0, (SCREEN_WIDTH as u32, SCREEN_HEIGHT as u32));
         
         // Initialize lines
-        let mut lines = Vec::with_capacity(rows);
+        let mut lines = VecDeque::with_capacity(rows);
         for _ in 0..rows {
-            lines.push(ScreenLine::new(cols));
+            lines.push_back(ScreenLine::new(cols));
         }
 
         Self {
             lines,
-            scrollback: Vec::new(),
+            scrollback: VecDeque::new(),
             viewport_offset: 0,
             max_scrollback: 200,
+            scroll_lock: false,
+            output_held: false,
+            line_spacing: 0,
             cursor_x: 0,
             cursor_y: 0,
             current_attrs: Attrs::default(),
+            saved_cursor: SavedCursor::default(),
+            primary_cursor: SavedCursor::default(),
+            primary_saved_cursor: SavedCursor::default(),
             font,
+            font_index: DEFAULT_FONT,
             rows,
             cols,
+            scroll_top: 0,
+            scroll_bottom: rows.saturating_sub(1),
+            tab_stops: default_tab_stops(cols),
             full_repaint: true,
+            pending_scroll: 0,
+            scroll_offset: 0,
+            hardware_scroll: true,
+            shadow: Vec::new(),
+            copy_mode: None,
+            clipboard: String::new(),
+            word_chars: DEFAULT_WORD_CHARS.into(),
+            blink_on: true,
+            title: heapless::String::new(),
+            status_bar: false,
+            status_text: heapless::String::new(),
+            status_dirty: false,
+            hyperlinks: heapless::Vec::new(),
+            next_hyperlink: 0,
+            primary_lines: None,
+            cursor_visible: true,
+            cursor_shape: CursorShape::Block,
+            charset: Charset::Ascii,
+            prev_cursor: None,
+            autowrap: true,
+            origin_mode: false,
+            bracketed_paste: false,
+            app_cursor_keys: false,
+            app_keypad: false,
+            mouse_tracking: MouseTracking::Off,
+            sgr_mouse: false,
+            focus_events: false,
+            unknown_modes: heapless::Vec::new(),
+            reverse_screen: false,
+            inverted: false,
+            center_grid: false,
+            rotation: Rotation::Deg0,
+            panel_width: SCREEN_WIDTH,
+            panel_height: SCREEN_HEIGHT,
+            wrap_pending: false,
+            last_char: None,
+            palette: Palette::DEFAULT,
+            user_palette: Palette::DEFAULT,
+            color_correction: ColorCorrection::IDENTITY,
+            replies: heapless::Vec::new(),
+            bell: false,
+            bell_shown: false,
         }
     }
 }
@@ -284,123 +1248,1668 @@ impl ScreenModel {
         }
         self.cursor_x = 0;
         self.cursor_y = 0;
+        self.wrap_pending = false;
         self.full_repaint = true;
     }
 
-    pub fn increase_font(&mut self) {
-        // TODO: implement font resizing
-    }
-
-    pub fn decrease_font(&mut self) {
-        // TODO: implement font resizing
-    }
-
-    fn scroll_up(&mut self) {
-        // Remove first line, add new line at end
-        if !self.lines.is_empty() {
-            let line = self.lines.remove(0);
-            self.scrollback.push(line);
-            if self.scrollback.len() > self.max_scrollback {
-                self.scrollback.remove(0);
-            }
-            self.lines.push(ScreenLine::new(self.cols));
-            self.full_repaint = true;
+    /// Discard all of the scrollback, returning the view to the screen
+    pub fn clear_scrollback(&mut self) {
+        if let Some(copy_mode) = &mut self.copy_mode {
+            copy_mode.discard_lines(self.scrollback.len());
         }
-    }
-
-    pub fn scroll_view_up(&mut self, n: usize) {
-        self.viewport_offset = (self.viewport_offset + n).min(self.scrollback.len());
+        self.scrollback.clear();
+        self.viewport_offset = 0;
         self.full_repaint = true;
     }
 
-    pub fn scroll_view_down(&mut self, n: usize) {
-        self.viewport_offset = self.viewport_offset.saturating_sub(n);
-        self.full_repaint = true;
+    /// RIS: return to the power-on state, keeping the user's settings
+    /// such as the configured font and scrollback size
+    fn reset(&mut self) {
+        let mut fresh = Self {
+            max_scrollback: self.max_scrollback,
+            scroll_lock: self.scroll_lock,
+            line_spacing: self.line_spacing,
+            font_index: self.font_index,
+            clipboard: core::mem::take(&mut self.clipboard),
+            word_chars: core::mem::take(&mut self.word_chars),
+            status_bar: self.status_bar,
+            inverted: self.inverted,
+            center_grid: self.center_grid,
+            rotation: self.rotation,
+            // The panel stays scrolled as it was, so drawing goes on
+            // from the same offset
+            scroll_offset: self.scroll_offset,
+            hardware_scroll: self.hardware_scroll,
+            palette: self.user_palette,
+            user_palette: self.user_palette,
+            color_correction: self.color_correction,
+            panel_width: self.panel_width,
+            panel_height: self.panel_height,
+            status_text: core::mem::take(&mut self.status_text),
+            replies: core::mem::take(&mut self.replies),
+            unknown_modes: core::mem::take(&mut self.unknown_modes),
+            ..Self::default()
+        };
+        fresh.set_font(FONTS[fresh.font_index]);
+        *self = fresh;
     }
 
-    pub fn reset_view(&mut self) {
-        if self.viewport_offset != 0 {
-            self.viewport_offset = 0;
-            self.full_repaint = true;
+    /// Blank the characters of line `y` from `start` to `end` that
+    /// DECSCA didn't protect. Unlike the other erases, the cells keep
+    /// their attributes.
+    fn selective_erase(&mut self, y: usize, start: usize, end: usize) {
+        let line = &mut self.lines[y];
+        for i in start..end {
+            if !line.attrs[i].protected() {
+                line.chars[i] = ' ';
+            }
         }
+        line.touch(start, end);
     }
 
-    pub fn set_max_scrollback(&mut self, max: usize) {
-        self.max_scrollback = max;
-        if self.scrollback.len() > max {
-            let remove_count = self.scrollback.len() - max;
-            self.scrollback.drain(0..remove_count);
-            // Adjust viewport offset if it's now out of bounds
-            if self.viewport_offset > self.scrollback.len() {
-                self.viewport_offset = self.scrollback.len();
+    /// DECSED: as ED 0, 1 and 2, sparing protected cells
+    fn selective_erase_display(&mut self, mode: u16) {
+        let (x, y) = (self.cursor_x, self.cursor_y);
+        match mode {
+            0 => {
+                self.selective_erase(y, x, self.cols);
+                for i in y + 1..self.rows {
+                    self.selective_erase(i, 0, self.cols);
+                }
+            }
+            1 => {
+                for i in 0..y {
+                    self.selective_erase(i, 0, self.cols);
+                }
+                self.selective_erase(y, 0, x + 1);
+            }
+            2 => {
+                for i in 0..self.rows {
+                    self.selective_erase(i, 0, self.cols);
+                }
             }
+            _ => {}
         }
     }
 
-    pub fn update_display(&mut self, display: &mut PicoCalcDisplay) {
-        if self.full_repaint {
-            display.clear(Rgb565::BLACK).unwrap();
-        }
+    /// DECSEL: as EL 0, 1 and 2, sparing protected cells
+    fn selective_erase_line(&mut self, mode: u16) {
+        let (start, end) = match mode {
+            0 => (self.cursor_x, self.cols),
+            1 => (0, self.cursor_x + 1),
+            2 => (0, self.cols),
+            _ => return,
+        };
+        self.selective_erase(self.cursor_y, start, end);
+    }
 
-        let font = self.font;
+    /// DECSTR: put the modes, attributes and cursor back to their
+    /// defaults while keeping the screen contents and scrollback
+    fn soft_reset(&mut self) {
+        self.current_attrs = Attrs::default();
+        self.saved_cursor = SavedCursor::default();
+        self.scroll_top = 0;
+        self.scroll_bottom = self.rows.saturating_sub(1);
+        self.origin_mode = false;
+        self.autowrap = true;
+        self.cursor_visible = true;
+        self.charset = Charset::Ascii;
+        self.app_cursor_keys = false;
+        self.app_keypad = false;
+        self.cursor_x = 0;
+        self.cursor_y = 0;
+        self.wrap_pending = false;
+    }
+
+    /// DECALN: fill the screen with `E`s to check the grid lines up
+    fn screen_alignment(&mut self) {
+        for line in self.lines.iter_mut() {
+            line.chars.fill('E');
+            line.attrs.fill(Attrs::default());
+            line.links.clear();
+        }
+        self.scroll_top = 0;
+        self.scroll_bottom = self.rows.saturating_sub(1);
+        self.cursor_x = 0;
+        self.cursor_y = 0;
+        self.wrap_pending = false;
+        self.full_repaint = true;
+    }
+
+    /// Switch to `font` and re-lay the grid to fit the panel.
+    /// The visible contents are discarded and the cursor is homed;
+    /// scrollback is retained at its original width.
+    fn set_font(&mut self, font: &'static MonoFont<'static>) {
+        let (cols, rows) = self.text_grid_size(font);
+        self.font = font;
+        self.cols = cols;
+        self.rows = rows;
+        self.lines.clear();
+        for _ in 0..rows {
+            self.lines.push_back(ScreenLine::new(cols));
+        }
+        if let Some(primary) = &mut self.primary_lines {
+            *primary = self.lines.clone();
+        }
+        self.scroll_top = 0;
+        self.scroll_bottom = rows.saturating_sub(1);
+        self.tab_stops = default_tab_stops(cols);
+        self.viewport_offset = 0;
+        self.cursor_x = 0;
+        self.cursor_y = 0;
+        self.wrap_pending = false;
+        self.full_repaint = true;
+    }
+
+    /// Returns the (cols, rows) of the grid for `font`, leaving out
+    /// the status bar row when it is shown
+    fn text_grid_size(&self, font: &MonoFont) -> (usize, usize) {
+        let (cols, rows) = grid_size(font, self.line_spacing, self.screen_size());
+        if self.status_bar {
+            (cols, rows.saturating_sub(1).max(1))
+        } else {
+            (cols, rows)
+        }
+    }
+
+    /// Reserve the bottom row of the panel for a status bar that the
+    /// firmware fills with `set_status_text`, or give it back to the grid
+    pub fn set_status_bar(&mut self, enabled: bool) {
+        if self.status_bar != enabled {
+            self.status_bar = enabled;
+            self.reflow(self.font);
+        }
+    }
+
+    /// Sets the text shown in the status bar, truncated to fit
+    pub fn set_status_text(&mut self, text: &str) {
+        if self.status_text == text {
+            return;
+        }
+        self.status_text.clear();
+        for c in text.chars() {
+            if self.status_text.push(c).is_err() {
+                break;
+            }
+        }
+        self.status_dirty = true;
+    }
+
+    /// DECCOLM: the panel can't fit a true 132 columns, so wide mode
+    /// uses the largest font that reaches 132 columns, or the smallest
+    /// font if none can, and `width()` reports the actual column count.
+    /// Leaving wide mode restores the configured font.
+    fn set_column_mode(&mut self, wide: bool) {
+        let font = if wide {
+            FONTS
+                .iter()
+                .rev()
+                .find(|font| grid_size(font, self.line_spacing, self.screen_size()).0 >= WIDE_COLUMNS)
+                .unwrap_or(&FONTS[0])
+        } else {
+            &FONTS[self.font_index]
+        };
+        self.set_font(font);
+    }
+
+    /// DECSET / DECRST (`CSI ? Pm h` / `CSI ? Pm l`). Modes we don't
+    /// handle are remembered in `unknown_modes` for debugging.
+    fn set_dec_modes(&mut self, params: &vte::Params, enable: bool) {
+        for param in params.iter() {
+            match param[0] {
+                1 => self.app_cursor_keys = enable,
+                3 => self.set_column_mode(enable),
+                5 => {
+                    if self.reverse_screen != enable {
+                        self.reverse_screen = enable;
+                        self.full_repaint = true;
+                    }
+                }
+                6 => self.set_origin_mode(enable),
+                7 => self.autowrap = enable,
+                1000 | 1002 if !enable => self.mouse_tracking = MouseTracking::Off,
+                1000 => self.mouse_tracking = MouseTracking::Normal,
+                1002 => self.mouse_tracking = MouseTracking::ButtonEvent,
+                1004 => self.focus_events = enable,
+                1006 => self.sgr_mouse = enable,
+                25 => self.cursor_visible = enable,
+                1049 => self.set_alt_screen(enable),
+                2004 => self.bracketed_paste = enable,
+                mode => {
+                    if !self.unknown_modes.contains(&mode) {
+                        self.unknown_modes.push(mode).ok();
+                    }
+                }
+            }
+        }
+    }
+
+    /// Private modes that programs tried to set or reset but that
+    /// aren't supported, oldest first
+    pub fn unknown_modes(&self) -> &[u16] {
+        &self.unknown_modes
+    }
+
+    /// DECSCUSR: blinking and steady variants are drawn the same
+    fn set_cursor_shape(&mut self, ps: u16) {
+        let shape = match ps {
+            0..=2 => CursorShape::Block,
+            3 | 4 => CursorShape::Underline,
+            5 | 6 => CursorShape::Bar,
+            _ => return,
+        };
+        if shape == self.cursor_shape {
+            return;
+        }
+        self.cursor_shape = shape;
+        // The old shape may not cover the whole cell it was drawn over
+        if let Some((x, y)) = self.prev_cursor
+            && y < self.rows
+        {
+            let view_start = self.view_start();
+            self.abs_line_mut(view_start + y).touch(x, x + 1);
+        }
+    }
+
+    /// Switches to or from the alternate screen, saving the cursor on
+    /// entry and restoring it on exit as xterm does for mode 1049
+    fn set_alt_screen(&mut self, enable: bool) {
+        if enable == self.primary_lines.is_some() {
+            return;
+        }
+        if enable {
+            self.primary_cursor = self.cursor_state();
+            self.primary_saved_cursor = core::mem::take(&mut self.saved_cursor);
+            let blank = (0..self.rows).map(|_| ScreenLine::new(self.cols)).collect();
+            self.primary_lines = Some(core::mem::replace(&mut self.lines, blank));
+        } else if let Some(primary) = self.primary_lines.take() {
+            self.lines = primary;
+            self.saved_cursor = self.primary_saved_cursor;
+            self.restore_cursor_state(self.primary_cursor);
+        }
+        self.viewport_offset = 0;
+        self.full_repaint = true;
+    }
+
+    pub fn increase_font(&mut self) {
+        if self.font_index + 1 < FONTS.len() {
+            self.font_index += 1;
+            self.reflow(FONTS[self.font_index]);
+        }
+    }
+
+    pub fn decrease_font(&mut self) {
+        if self.font_index > 0 {
+            self.font_index -= 1;
+            self.reflow(FONTS[self.font_index]);
+        }
+    }
+
+    /// Sets the gap in pixels between rows, up to 8, which is painted
+    /// in the background color. Fewer rows fit with a larger gap; the
+    /// screen contents are kept as for a resize.
+    pub fn set_line_spacing(&mut self, spacing: u32) {
+        let spacing = spacing.min(MAX_LINE_SPACING);
+        if self.line_spacing != spacing {
+            self.line_spacing = spacing;
+            self.reflow(self.font);
+        }
+    }
+
+    pub fn line_spacing(&self) -> u32 {
+        self.line_spacing
+    }
+
+    /// Height in pixels of a row, including the gap below it
+    fn cell_height(&self) -> u32 {
+        self.font.character_size.height + self.line_spacing
+    }
+
+    /// Re-lay the grid for `font` while keeping the screen contents
+    fn reflow(&mut self, font: &'static MonoFont<'static>) {
+        let (cols, rows) = self.text_grid_size(font);
+        self.font = font;
+        self.resize(cols, rows);
+    }
+
+    /// Change the grid to `cols` by `rows` while keeping the screen
+    /// contents. Lines are truncated or padded to the new width rather
+    /// than rewrapped, and stay top-aligned; when there are fewer rows,
+    /// lines from the top move into scrollback so that the cursor
+    /// row stays on screen. The scroll region is reset to the whole
+    /// screen and the tab stops to their defaults.
+    pub fn resize(&mut self, cols: usize, rows: usize) {
+        let (cols, rows) = (cols.max(1), rows.max(1));
+        self.cols = cols;
+        self.rows = rows;
+
+        let excess = (self.cursor_y + 1).saturating_sub(rows);
+        let moved: Vec<ScreenLine> = self.lines.drain(..excess).collect();
+        if self.primary_lines.is_none() {
+            for line in moved {
+                self.push_scrollback(line);
+            }
+        }
+        self.cursor_y -= excess;
+
+        let screens = self.primary_lines.iter_mut().chain(core::iter::once(&mut self.lines));
+        for lines in screens {
+            lines.truncate(rows);
+            for line in lines.iter_mut() {
+                line.resize(cols);
+            }
+            while lines.len() < rows {
+                lines.push_back(ScreenLine::new(cols));
+            }
+        }
+
+        self.scroll_top = 0;
+        self.scroll_bottom = rows.saturating_sub(1);
+        self.tab_stops = default_tab_stops(cols);
+        self.viewport_offset = 0;
+        self.cursor_x = self.cursor_x.min(cols.saturating_sub(1));
+        self.cursor_y = self.cursor_y.min(rows.saturating_sub(1));
+        self.wrap_pending = false;
+        self.full_repaint = true;
+    }
+
+    fn is_full_scroll_region(&self) -> bool {
+        self.scroll_top == 0 && self.scroll_bottom + 1 == self.rows
+    }
+
+    /// Scroll the lines of the scroll region up by one.
+    /// The line leaving the top is moved to scrollback only when
+    /// the region covers the whole primary screen, and only a
+    /// whole-screen scroll uses the panel's hardware scrolling.
+    fn scroll_up(&mut self) {
+        let (top, bottom) = (self.scroll_top, self.scroll_bottom);
+        if top > bottom || bottom >= self.lines.len() {
+            return;
+        }
+        let Some(line) = self.lines.remove(top) else {
+            return;
+        };
+        self.lines.insert(bottom, ScreenLine::new(self.cols));
+        if self.is_full_scroll_region() {
+            if self.primary_lines.is_none() {
+                self.push_scrollback(line);
+            }
+            // The panel can scroll what it shows by itself, which
+            // leaves just the new bottom line to draw
+            if self.viewport_offset == 0 && self.copy_mode.is_none() {
+                self.pending_scroll += 1;
+            } else {
+                self.full_repaint = true;
+            }
+        } else {
+            for line in self.lines.range_mut(top..=bottom) {
+                line.touch_all();
+            }
+        }
+    }
+
+    fn push_scrollback(&mut self, line: ScreenLine) {
+        self.scrollback.push_back(line);
+        if self.scrollback.len() > self.max_scrollback {
+            self.scrollback.pop_front();
+            if let Some(copy_mode) = &mut self.copy_mode {
+                copy_mode.discard_lines(1);
+            }
+        }
+        // A view held back by scroll lock keeps showing the same lines
+        if self.scroll_lock && self.viewport_offset > 0 && self.copy_mode.is_none() {
+            self.viewport_offset = (self.viewport_offset + 1).min(self.scrollback.len());
+        }
+    }
+
+    /// SD: scroll the lines of the scroll region down by `n`, adding
+    /// blank lines at the top. Lines leaving the bottom are dropped.
+    fn scroll_down(&mut self, n: usize) {
+        let (top, bottom) = (self.scroll_top, self.scroll_bottom);
+        if top > bottom || bottom >= self.lines.len() {
+            return;
+        }
+        for _ in 0..n.min(bottom + 1 - top) {
+            self.lines.remove(bottom);
+            self.lines.insert(top, ScreenLine::new(self.cols));
+        }
+        for line in self.lines.range_mut(top..=bottom) {
+            line.touch_all();
+        }
+    }
+
+    /// IL: insert `n` blank lines at the cursor row, pushing the lines
+    /// below it down and off the bottom of the scroll region
+    fn insert_lines(&mut self, n: usize) {
+        let (y, bottom) = (self.cursor_y, self.scroll_bottom);
+        if y < self.scroll_top || y > bottom {
+            return;
+        }
+        for _ in 0..n.min(bottom + 1 - y) {
+            self.lines.remove(bottom);
+            self.lines.insert(y, ScreenLine::new(self.cols));
+        }
+        for line in self.lines.range_mut(y..=bottom) {
+            line.touch_all();
+        }
+        self.cursor_x = 0;
+    }
+
+    /// DL: delete `n` lines at the cursor row, pulling the lines below
+    /// it up and adding blank lines at the bottom of the scroll region
+    fn delete_lines(&mut self, n: usize) {
+        let (y, bottom) = (self.cursor_y, self.scroll_bottom);
+        if y < self.scroll_top || y > bottom {
+            return;
+        }
+        for _ in 0..n.min(bottom + 1 - y) {
+            self.lines.remove(y);
+            self.lines.insert(bottom, ScreenLine::new(self.cols));
+        }
+        for line in self.lines.range_mut(y..=bottom) {
+            line.touch_all();
+        }
+        self.cursor_x = 0;
+    }
+
+    /// Move the cursor down a line, scrolling if it is at the
+    /// bottom of the scroll region
+    fn line_feed(&mut self) {
+        self.finalize_line(self.cursor_y);
+        if self.cursor_y == self.scroll_bottom {
+            self.scroll_up();
+        } else if self.cursor_y + 1 < self.rows {
+            self.cursor_y += 1;
+        }
+    }
+
+    /// Columns available on the cursor's line, which is fewer on a
+    /// double width line
+    fn cursor_line_cols(&self) -> usize {
+        let size = self.lines.get(self.cursor_y).map_or(LineSize::Single, |line| line.size);
+        line_cols(self.cols, size)
+    }
+
+    /// DECDWL / DECDHL / DECSWL: change the size of the cursor's line
+    fn set_line_size(&mut self, size: LineSize) {
+        if let Some(line) = self.lines.get_mut(self.cursor_y)
+            && line.size != size
+        {
+            line.size = size;
+            line.touch_all();
+        }
+        self.cursor_x = self.cursor_x.min(self.cursor_line_cols().saturating_sub(1));
+    }
+
+    /// Move the cursor up a line, scrolling the region down if it
+    /// is at the top of the scroll region
+    fn reverse_index(&mut self) {
+        if self.cursor_y == self.scroll_top {
+            self.scroll_down(1);
+        } else if self.cursor_y > 0 {
+            self.cursor_y -= 1;
+        }
+    }
+
+    fn save_cursor(&mut self) {
+        self.saved_cursor = self.cursor_state();
+    }
+
+    fn restore_cursor(&mut self) {
+        self.restore_cursor_state(self.saved_cursor);
+    }
+
+    /// The cursor position along with the pen it prints with
+    fn cursor_state(&self) -> SavedCursor {
+        SavedCursor {
+            cursor_x: self.cursor_x,
+            cursor_y: self.cursor_y,
+            attrs: self.current_attrs,
+            charset: self.charset,
+        }
+    }
+
+    /// Restore a saved cursor, clamping it to the grid in case
+    /// the screen was resized since it was saved
+    fn restore_cursor_state(&mut self, saved: SavedCursor) {
+        self.cursor_x = saved.cursor_x.min(self.cols.saturating_sub(1));
+        self.cursor_y = saved.cursor_y.min(self.rows.saturating_sub(1));
+        self.current_attrs = saved.attrs;
+        self.charset = saved.charset;
+        self.wrap_pending = false;
+    }
+
+    /// DECSTBM: set the scroll region from 1-based, inclusive rows.
+    /// Omitted params select the full screen.
+    fn set_scroll_region(&mut self, params: &vte::Params) {
+        let mut iter = params.iter();
+        let top = iter.next().map(|p| p[0]).unwrap_or(0).max(1) as usize - 1;
+        let bottom = match iter.next().map(|p| p[0]).unwrap_or(0) {
+            0 => self.rows,
+            n => (n as usize).min(self.rows),
+        };
+        if top + 1 >= bottom {
+            return;
+        }
+        self.scroll_top = top;
+        self.scroll_bottom = bottom - 1;
+        self.cursor_x = 0;
+        self.set_cursor_row(0);
+    }
+
+    /// DECOM: switching origin mode either way homes the cursor
+    fn set_origin_mode(&mut self, enable: bool) {
+        self.origin_mode = enable;
+        self.cursor_x = 0;
+        self.set_cursor_row(0);
+    }
+
+    /// Move the cursor to a 0-based row as addressed by CUP/VPA,
+    /// which in origin mode is relative to and confined by the
+    /// scroll region
+    fn set_cursor_row(&mut self, row: usize) {
+        self.cursor_y = if self.origin_mode {
+            (self.scroll_top + row).min(self.scroll_bottom)
+        } else {
+            row.min(self.rows.saturating_sub(1))
+        };
+    }
+
+    pub fn scroll_view_up(&mut self, n: usize) {
+        self.viewport_offset = (self.viewport_offset + n).min(self.scrollback.len());
+        self.full_repaint = true;
+    }
+
+    pub fn scroll_view_down(&mut self, n: usize) {
+        self.viewport_offset = self.viewport_offset.saturating_sub(n);
+        self.full_repaint = true;
+    }
+
+    /// Scroll the view back a page, keeping one line of overlap
+    pub fn scroll_view_page_up(&mut self) {
+        self.scroll_view_up(self.rows.saturating_sub(1).max(1));
+    }
+
+    /// Scroll the view forward a page, keeping one line of overlap
+    pub fn scroll_view_page_down(&mut self) {
+        self.scroll_view_down(self.rows.saturating_sub(1).max(1));
+    }
+
+    /// Scroll the view back to the oldest line of scrollback
+    pub fn scroll_view_to_top(&mut self) {
+        self.scroll_view_up(self.scrollback.len());
+    }
+
+    /// Scroll the view forward to the live screen
+    pub fn scroll_view_to_bottom(&mut self) {
+        self.scroll_view_down(self.viewport_offset);
+    }
+
+    pub fn reset_view(&mut self) {
+        // Copy mode owns the viewport until it is exited
+        if self.viewport_offset != 0 && self.copy_mode.is_none() {
+            if self.scroll_lock {
+                if !self.output_held {
+                    self.output_held = true;
+                    self.full_repaint = true;
+                }
+                return;
+            }
+            self.viewport_offset = 0;
+            self.full_repaint = true;
+        }
+    }
+
+    /// With scroll lock on, output no longer returns a view scrolled
+    /// back through history to the bottom; the view stays on the same
+    /// lines until the user scrolls back down.
+    pub fn set_scroll_lock(&mut self, enabled: bool) {
+        self.scroll_lock = enabled;
+    }
+
+    pub fn scroll_lock(&self) -> bool {
+        self.scroll_lock
+    }
+
+    pub fn set_max_scrollback(&mut self, max: usize) {
+        self.max_scrollback = max;
+        if self.scrollback.len() > max {
+            let remove_count = self.scrollback.len() - max;
+            self.scrollback.drain(0..remove_count);
+            if let Some(copy_mode) = &mut self.copy_mode {
+                copy_mode.discard_lines(remove_count);
+            }
+            // Adjust viewport offset if it's now out of bounds
+            if self.viewport_offset > self.scrollback.len() {
+                self.viewport_offset = self.scrollback.len();
+            }
+        }
+    }
+
+    /// Index of the line shown at the top of the viewport, in the
+    /// combined scrollback and screen lines
+    fn view_start(&self) -> usize {
+        self.scrollback.len().saturating_sub(self.viewport_offset)
+    }
+
+    fn total_lines(&self) -> usize {
+        self.scrollback.len() + self.lines.len()
+    }
+
+    fn abs_line(&self, line: usize) -> &ScreenLine {
+        if line < self.scrollback.len() {
+            &self.scrollback[line]
+        } else {
+            &self.lines[line - self.scrollback.len()]
+        }
+    }
+
+    fn abs_line_mut(&mut self, line: usize) -> &mut ScreenLine {
+        if line < self.scrollback.len() {
+            &mut self.scrollback[line]
+        } else {
+            &mut self.lines[line - self.scrollback.len()]
+        }
+    }
+
+    fn abs_char(&self, pos: AbsPos) -> char {
+        self.abs_line(pos.line).chars.get(pos.col).copied().unwrap_or(' ')
+    }
+
+    fn next_pos(&self, pos: AbsPos) -> Option<AbsPos> {
+        if pos.col + 1 < self.abs_line(pos.line).chars.len() {
+            Some(AbsPos { line: pos.line, col: pos.col + 1 })
+        } else if pos.line + 1 < self.total_lines() {
+            Some(AbsPos { line: pos.line + 1, col: 0 })
+        } else {
+            None
+        }
+    }
+
+    fn prev_pos(&self, pos: AbsPos) -> Option<AbsPos> {
+        if pos.col > 0 {
+            Some(AbsPos { line: pos.line, col: pos.col - 1 })
+        } else if pos.line > 0 {
+            let col = self.abs_line(pos.line - 1).chars.len().saturating_sub(1);
+            Some(AbsPos { line: pos.line - 1, col })
+        } else {
+            None
+        }
+    }
+
+    fn is_word_char(&self, c: char) -> bool {
+        c.is_alphanumeric() || self.word_chars.contains(c)
+    }
+
+    /// Set the non-alphanumeric characters that are considered part
+    /// of a word by word_at and copy mode word motion
+    pub fn set_word_chars(&mut self, chars: &str) {
+        self.word_chars = chars.into();
+    }
+
+    /// Returns the half-open range of columns spanned by the word
+    /// under the viewport cell at x, y, or None if that cell isn't
+    /// part of a word
+    pub fn word_at(&self, x: usize, y: usize) -> Option<(usize, usize)> {
+        if y >= self.rows {
+            return None;
+        }
+        let chars = &self.abs_line(self.view_start() + y).chars;
+        if !self.is_word_char(*chars.get(x)?) {
+            return None;
+        }
+        let start = chars[..x]
+            .iter()
+            .rposition(|&c| !self.is_word_char(c))
+            .map_or(0, |i| i + 1);
+        let end = chars[x..]
+            .iter()
+            .position(|&c| !self.is_word_char(c))
+            .map_or(chars.len(), |i| x + i);
+        Some((start, end))
+    }
+
+    /// Returns the start of the next word, crossing lines as needed
+    fn word_forward(&self, mut pos: AbsPos) -> AbsPos {
+        let mut in_word = self.is_word_char(self.abs_char(pos));
+        while let Some(next) = self.next_pos(pos) {
+            let word = self.is_word_char(self.abs_char(next));
+            let new_line = next.line != pos.line;
+            pos = next;
+            if word && (!in_word || new_line) {
+                break;
+            }
+            in_word = word;
+        }
+        pos
+    }
+
+    /// Returns the start of the current word, or of the prior
+    /// word if already at the start of a word
+    fn word_backward(&self, mut pos: AbsPos) -> AbsPos {
+        while let Some(prev) = self.prev_pos(pos) {
+            pos = prev;
+            if self.is_word_char(self.abs_char(pos)) {
+                break;
+            }
+        }
+        while let Some(prev) = self.prev_pos(pos) {
+            if prev.line != pos.line || !self.is_word_char(self.abs_char(prev)) {
+                break;
+            }
+            pos = prev;
+        }
+        pos
+    }
+
+    /// Called when output moves off a line, to record the URLs it contains
+    fn finalize_line(&mut self, y: usize) {
+        if let Some(line) = self.lines.get_mut(y) {
+            line.links = scan_links(&line.chars);
+        }
+    }
+
+    /// Returns the half-open column spans of the URLs that were
+    /// found on viewport row y
+    pub fn links_on_line(&self, y: usize) -> impl Iterator<Item = (usize, usize)> + '_ {
+        let links = if y < self.rows {
+            &self.abs_line(self.view_start() + y).links[..]
+        } else {
+            &[]
+        };
+        links
+            .iter()
+            .map(|&(start, end)| (start as usize, end as usize))
+    }
+
+    /// Returns the character and attributes at viewport column x of
+    /// row y, so while scrolled back this reads scrollback. The right
+    /// half of a wide character reads as `'\0'`.
+    pub fn cell(&self, x: usize, y: usize) -> Option<(char, Attrs)> {
+        if y >= self.rows {
+            return None;
+        }
+        let line = self.abs_line(self.view_start() + y);
+        Some((*line.chars.get(x)?, *line.attrs.get(x)?))
+    }
+
+    /// Returns the cells of each viewport row, top to bottom. The
+    /// right half of a wide character is `'\0'`.
+    pub fn iter_lines(&self) -> impl Iterator<Item = &[char]> + '_ {
+        let start = self.view_start();
+        (start..start + self.rows).map(move |line| &self.abs_line(line).chars[..])
+    }
+
+    /// Returns the text of the viewport, with rows separated by
+    /// newlines and trailing spaces trimmed from each
+    pub fn to_text(&self) -> String {
+        let mut text = String::new();
+        for (y, chars) in self.iter_lines().enumerate() {
+            if y > 0 {
+                text.push('\n');
+            }
+            let start = text.len();
+            text.extend(chars.iter().filter(|&&c| c != WIDE_CONTINUATION));
+            text.truncate(start + text[start..].trim_end_matches(' ').len());
+        }
+        text
+    }
+
+    /// Returns the target of the OSC 8 hyperlink at viewport
+    /// column x of row y, if any
+    pub fn link_at(&self, x: usize, y: usize) -> Option<&str> {
+        if y >= self.rows {
+            return None;
+        }
+        let link = self.abs_line(self.view_start() + y).attrs.get(x)?.link?;
+        self.hyperlinks.get(link as usize).map(|uri| uri.as_str())
+    }
+
+    /// Select the next (or previous) URL relative to the copy mode cursor
+    pub fn copy_mode_select_link(&mut self, forward: bool) {
+        let Some(copy_mode) = &self.copy_mode else {
+            return;
+        };
+        let cursor = copy_mode.cursor;
+        let sel_start = copy_mode.selection().map_or(cursor, |(start, _)| start);
+        let spans = |line: usize| {
+            self.abs_line(line).links.iter().map(move |&(start, end)| {
+                let start = AbsPos {
+                    line,
+                    col: start as usize,
+                };
+                let end = AbsPos {
+                    line,
+                    col: end as usize - 1,
+                };
+                (start, end)
+            })
+        };
+        let found = if forward {
+            (cursor.line..self.total_lines())
+                .flat_map(spans)
+                .find(|(start, _)| *start > cursor)
+        } else {
+            (0..=sel_start.line)
+                .rev()
+                .flat_map(|line| spans(line).rev())
+                .find(|(start, _)| *start < sel_start)
+        };
+
+        if let Some((start, end)) = found {
+            self.copy_mode = Some(CopyMode {
+                cursor: end,
+                anchor: Some(start),
+                block: false,
+            });
+            self.copy_mode_reveal(end.line);
+            self.full_repaint = true;
+        }
+    }
+
+    pub fn in_copy_mode(&self) -> bool {
+        self.copy_mode.is_some()
+    }
+
+    /// Enter copy mode with the selection cursor placed at the terminal cursor
+    pub fn enter_copy_mode(&mut self) {
+        let cursor = AbsPos {
+            line: self.scrollback.len() + self.cursor_y.min(self.rows.saturating_sub(1)),
+            col: self.cursor_x.min(self.cols.saturating_sub(1)),
+        };
+        self.copy_mode = Some(CopyMode {
+            cursor,
+            anchor: None,
+            block: false,
+        });
+        self.full_repaint = true;
+    }
+
+    /// Leave copy mode, optionally copying the selection into the clipboard
+    pub fn exit_copy_mode(&mut self, yank: bool) {
+        if yank
+            && let Some(text) = self.selection_text()
+        {
+            self.clipboard = text;
+        }
+        self.copy_mode = None;
+        self.reset_view();
+        self.full_repaint = true;
+    }
+
+    /// Start a selection at the copy mode cursor, or cancel the current one
+    pub fn copy_mode_toggle_selection(&mut self) {
+        if let Some(copy_mode) = &mut self.copy_mode {
+            copy_mode.anchor = match copy_mode.anchor {
+                Some(_) => None,
+                None => Some(copy_mode.cursor),
+            };
+            self.full_repaint = true;
+        }
+    }
+
+    /// Switch between linear and block selection, starting a
+    /// selection at the copy mode cursor if there isn't one
+    pub fn copy_mode_toggle_block(&mut self) {
+        if let Some(copy_mode) = &mut self.copy_mode {
+            copy_mode.block = !copy_mode.block;
+            if copy_mode.anchor.is_none() {
+                copy_mode.anchor = Some(copy_mode.cursor);
+            }
+            self.full_repaint = true;
+        }
+    }
+
+    pub fn copy_mode_move(&mut self, motion: CopyMotion) {
+        let Some(copy_mode) = &self.copy_mode else {
+            return;
+        };
+        let pos = copy_mode.cursor;
+        let last_line = self.total_lines().saturating_sub(1);
+        let last_col = |model: &Self, line: usize| {
+            model.abs_line(line).chars.len().saturating_sub(1)
+        };
+
+        let pos = match motion {
+            CopyMotion::Left => AbsPos {
+                line: pos.line,
+                col: pos.col.saturating_sub(1),
+            },
+            CopyMotion::Right => AbsPos {
+                line: pos.line,
+                col: (pos.col + 1).min(last_col(self, pos.line)),
+            },
+            CopyMotion::Up => {
+                let line = pos.line.saturating_sub(1);
+                AbsPos {
+                    line,
+                    col: pos.col.min(last_col(self, line)),
+                }
+            }
+            CopyMotion::Down => {
+                let line = (pos.line + 1).min(last_line);
+                AbsPos {
+                    line,
+                    col: pos.col.min(last_col(self, line)),
+                }
+            }
+            CopyMotion::WordForward => self.word_forward(pos),
+            CopyMotion::WordBackward => self.word_backward(pos),
+            CopyMotion::LineStart => AbsPos {
+                line: pos.line,
+                col: 0,
+            },
+            CopyMotion::LineEnd => {
+                let chars = &self.abs_line(pos.line).chars;
+                AbsPos {
+                    line: pos.line,
+                    col: chars.iter().rposition(|&c| c != ' ').unwrap_or(0),
+                }
+            }
+        };
+
+        self.copy_mode_reveal(pos.line);
+        if let Some(copy_mode) = &mut self.copy_mode {
+            copy_mode.cursor = pos;
+        }
+        self.full_repaint = true;
+    }
+
+    /// Scroll the viewport so that `line` is visible
+    fn copy_mode_reveal(&mut self, line: usize) {
+        let view_start = self.view_start();
+        if line < view_start {
+            self.viewport_offset = self.scrollback.len() - line;
+        } else if line >= view_start + self.rows {
+            self.viewport_offset = (self.scrollback.len() + self.rows)
+                .saturating_sub(line + 1)
+                .min(self.scrollback.len());
+        }
+    }
+
+    /// Returns the inclusive range of selected columns on `line`
+    fn selection_on_line(&self, line: usize) -> Option<(usize, usize)> {
+        let copy_mode = self.copy_mode.as_ref()?;
+        let (start, end) = copy_mode.selection()?;
+        if line < start.line || line > end.line {
+            return None;
+        }
+        if copy_mode.block {
+            let (a, b) = (start.col, end.col);
+            return Some((a.min(b), a.max(b)));
+        }
+        let first = if line == start.line { start.col } else { 0 };
+        let last = if line == end.line { end.col } else { usize::MAX };
+        Some((first, last))
+    }
+
+    /// Returns the selected text, with trailing spaces trimmed from each line.
+    /// A block selection yields the selected columns of each row.
+    fn selection_text(&self) -> Option<String> {
+        let (start, end) = self.copy_mode.as_ref()?.selection()?;
+        let mut text = String::new();
+        for line in start.line..=end.line {
+            let (first, last) = self.selection_on_line(line)?;
+            let chars = &self.abs_line(line).chars;
+            let last = last.min(chars.len().saturating_sub(1));
+            if line != start.line {
+                text.push('\n');
+            }
+            if first <= last {
+                let slice: String = chars[first..=last]
+                    .iter()
+                    .filter(|&&c| c != WIDE_CONTINUATION)
+                    .collect();
+                text.push_str(slice.trim_end());
+            }
+        }
+        Some(text)
+    }
+
+    /// Returns the most recently yanked copy mode selection
+    pub fn clipboard(&self) -> &str {
+        &self.clipboard
+    }
+
+    /// Flips the blink phase and marks the visible lines holding
+    /// blinking cells for redraw
+    pub fn toggle_blink(&mut self) {
+        self.blink_on = !self.blink_on;
+        let view_start = self.view_start();
+        for abs_idx in view_start..view_start + self.rows {
+            let line = self.abs_line_mut(abs_idx);
+            for x in 0..line.attrs.len() {
+                if line.attrs[x].blink() {
+                    line.touch(x, x + 1);
+                }
+            }
+        }
+    }
+
+    /// Queues a reply to a host query such as DSR; replies that
+    /// don't fit in the queue are dropped
+    fn reply(&mut self, args: fmt::Arguments) {
+        let mut text = heapless::String::<32>::new();
+        if fmt::write(&mut text, args).is_ok() {
+            self.replies.extend_from_slice(text.as_bytes()).ok();
+        }
+    }
+
+    /// Takes the queued replies to host queries. Whoever feeds host
+    /// output to `parse_bytes` should write these back to the host
+    /// afterwards; the ssh session does so after each read.
+    pub fn take_replies(&mut self) -> heapless::Vec<u8, MAX_REPLY_LEN> {
+        core::mem::take(&mut self.replies)
+    }
+
+    /// Set the colors, in place of any that programs changed. They
+    /// are kept across resets.
+    pub fn set_palette(&mut self, palette: Palette) {
+        self.palette = palette;
+        self.user_palette = palette;
+        self.full_repaint = true;
+    }
+
+    /// Invert the colors of everything drawn, including the cursor,
+    /// as a night mode. This is separate from the reverse video that
+    /// programs can ask for, and is kept across resets.
+    pub fn set_inverted(&mut self, inverted: bool) {
+        if self.inverted != inverted {
+            self.inverted = inverted;
+            self.full_repaint = true;
+        }
+    }
+
+    pub fn inverted(&self) -> bool {
+        self.inverted
+    }
+
+    /// Center the grid on the panel, with the default background in
+    /// the margins, rather than leaving all the spare pixels to the
+    /// right of and below it
+    pub fn set_center_grid(&mut self, enabled: bool) {
+        if self.center_grid != enabled {
+            self.center_grid = enabled;
+            self.full_repaint = true;
+        }
+    }
+
+    /// Use the panel's hardware scroll for full-screen scrolls, or
+    /// repaint the screen for each when the panel can't do it
+    pub fn set_hardware_scroll(&mut self, enabled: bool) {
+        self.hardware_scroll = enabled;
+    }
+
+    /// Turn the screen on the panel. Turned sideways, the grid is
+    /// re-laid to fit the panel's height across and width down.
+    pub fn set_rotation(&mut self, rotation: Rotation) {
+        if self.rotation != rotation {
+            let sideways = self.rotation.is_sideways() != rotation.is_sideways();
+            self.rotation = rotation;
+            if sideways {
+                self.reflow(self.font);
+            }
+            self.full_repaint = true;
+        }
+    }
+
+    pub fn rotation(&self) -> Rotation {
+        self.rotation
+    }
+
+    /// Set the size in pixels of the panel being drawn to, for
+    /// panels other than the PicoCalc's. Hardware scrolling limits
+    /// the height to the panel's frame memory, and the width is
+    /// limited to `MAX_PANEL_WIDTH`.
+    pub fn set_panel_size(&mut self, width: u16, height: u16) {
+        let (width, height) = (width.clamp(1, MAX_PANEL_WIDTH), height.clamp(1, FRAME_HEIGHT as u16));
+        if (self.panel_width, self.panel_height) != (width, height) {
+            self.panel_width = width;
+            self.panel_height = height;
+            self.reflow(self.font);
+        }
+    }
+
+    /// Width and height in pixels of the screen the grid is laid out
+    /// on, which is the panel turned by the rotation
+    pub fn screen_size(&self) -> (u32, u32) {
+        let (width, height) = (self.panel_width as u32, self.panel_height as u32);
+        if self.rotation.is_sideways() {
+            (height, width)
+        } else {
+            (width, height)
+        }
+    }
+
+    /// Where on the panel the top left of the grid is drawn. The
+    /// status bar, when shown, stays directly below the grid.
+    fn grid_origin(&self) -> (u32, u32) {
+        if !self.center_grid {
+            return (0, 0);
+        }
+        let width = self.cols as u32 * (self.font.character_size.width + self.font.character_spacing);
+        let rows = self.rows + self.status_bar as usize;
+        let height = rows as u32 * self.cell_height();
+        let (screen_width, screen_height) = self.screen_size();
+        (
+            screen_width.saturating_sub(width) / 2,
+            screen_height.saturating_sub(height) / 2,
+        )
+    }
+
+    /// Set the correction applied to 24-bit and 256-color colors; the
+    /// palette's own colors are used as they are. It is kept across
+    /// resets.
+    pub fn set_color_correction(&mut self, correction: ColorCorrection) {
+        self.color_correction = correction;
+        self.full_repaint = true;
+    }
+
+    /// OSC 4: change palette entries from `index;spec` pairs. Only the
+    /// 16 named colors are kept in the palette, so higher indexes
+    /// are ignored.
+    fn set_palette_colors(&mut self, pairs: &[&[u8]]) {
+        for pair in pairs.chunks_exact(2) {
+            let index = core::str::from_utf8(pair[0]).ok().and_then(|i| i.parse::<usize>().ok());
+            let (Some(index), Some(color)) = (index, parse_color_spec(pair[1])) else {
+                continue;
+            };
+            if let Some(entry) = self.palette.ansi.get_mut(index) {
+                *entry = color;
+                self.full_repaint = true;
+            }
+        }
+    }
+
+    /// OSC 10, 11 and 12: set or, given `?`, report the default
+    /// foreground, default background and cursor colors. Further specs
+    /// move on to the next color, as in xterm.
+    fn set_dynamic_colors(&mut self, first: usize, specs: &[&[u8]], bell_terminated: bool) {
+        for (number, spec) in (first..=12).zip(specs) {
+            let slot = match number {
+                10 => &mut self.palette.fg,
+                11 => &mut self.palette.bg,
+                _ => &mut self.palette.cursor,
+            };
+            if *spec == b"?" {
+                let color = Rgb888::from(*slot);
+                let terminator = if bell_terminated { "\x07" } else { "\x1b\\" };
+                self.reply(format_args!(
+                    "\x1b]{0};rgb:{1:02x}{1:02x}/{2:02x}{2:02x}/{3:02x}{3:02x}{4}",
+                    number, color.r(), color.g(), color.b(), terminator,
+                ));
+            } else if let Some(color) = parse_color_spec(spec) {
+                *slot = color;
+                self.full_repaint = true;
+            }
+        }
+    }
+
+    /// OSC 8: start or, with an empty target, end a hyperlink.
+    /// The target may itself contain `;`, which vte splits on.
+    fn set_hyperlink(&mut self, uri: &[&[u8]]) {
+        self.current_attrs.link = None;
+        let mut target = heapless::String::<MAX_HYPERLINK_LEN>::new();
+        for (i, part) in uri.iter().enumerate() {
+            let Ok(part) = core::str::from_utf8(part) else {
+                return;
+            };
+            if (i > 0 && target.push(';').is_err()) || target.push_str(part).is_err() {
+                return;
+            }
+        }
+        if target.is_empty() {
+            return;
+        }
+
+        let index = if let Some(index) = self.hyperlinks.iter().position(|t| *t == target) {
+            index
+        } else if self.hyperlinks.push(target.clone()).is_ok() {
+            self.hyperlinks.len() - 1
+        } else {
+            let index = self.next_hyperlink;
+            self.forget_hyperlink(index as u8);
+            self.hyperlinks[index] = target;
+            self.next_hyperlink = (index + 1) % MAX_HYPERLINKS;
+            index
+        };
+        self.current_attrs.link = Some(index as u8);
+    }
+
+    /// Unlinks the cells and saved pens referring to hyperlink entry
+    /// `link`, so that they don't take on the target it's reused for
+    fn forget_hyperlink(&mut self, link: u8) {
+        let lines = self
+            .lines
+            .iter_mut()
+            .chain(self.scrollback.iter_mut())
+            .chain(self.primary_lines.iter_mut().flatten());
+        for line in lines {
+            for x in 0..line.attrs.len() {
+                if line.attrs[x].link == Some(link) {
+                    line.attrs[x].link = None;
+                    line.touch(x, x + 1);
+                }
+            }
+        }
+        let saved = [
+            &mut self.saved_cursor,
+            &mut self.primary_cursor,
+            &mut self.primary_saved_cursor,
+        ];
+        for cursor in saved {
+            if cursor.attrs.link == Some(link) {
+                cursor.attrs.link = None;
+            }
+        }
+    }
+
+    /// Whether the running program asked, with `CSI ? 2004 h`, for
+    /// pasted text to be wrapped in `ESC [ 200 ~` and `ESC [ 201 ~`
+    /// so that it can tell a paste from typing
+    pub fn bracketed_paste(&self) -> bool {
+        self.bracketed_paste
+    }
+
+    /// Whether the running program asked, with `CSI ? 1 h`, for the
+    /// cursor keys to send `ESC O A` rather than `ESC [ A` and so on
+    pub fn app_cursor_keys(&self) -> bool {
+        self.app_cursor_keys
+    }
+
+    /// Whether the running program put the keypad in application
+    /// mode with `ESC =`; `ESC >` puts it back to numeric mode
+    pub fn app_keypad(&self) -> bool {
+        self.app_keypad
+    }
+
+    /// Tells the running program, if it asked with `CSI ? 1004 h`,
+    /// that the terminal gained or lost focus, such as when a menu
+    /// is drawn over it. The report is queued with the replies.
+    pub fn report_focus(&mut self, focused: bool) {
+        if !self.focus_events {
+            return;
+        }
+        let report: &[u8] = if focused { b"\x1b[I" } else { b"\x1b[O" };
+        self.replies.extend_from_slice(report).ok();
+    }
+
+    /// Which mouse events the running program wants reported
+    pub fn mouse_tracking(&self) -> MouseTracking {
+        self.mouse_tracking
+    }
+
+    /// Returns the input to send to the host to report a mouse event
+    /// at cell (`x`, `y`), or an empty string if the program didn't
+    /// ask for mouse reports. `button` is 0-2 for the left, middle
+    /// and right buttons and 64/65 for the wheel, plus 32 for motion
+    /// with the button held.
+    pub fn encode_mouse(
+        &self,
+        button: u8,
+        x: usize,
+        y: usize,
+        pressed: bool,
+    ) -> heapless::String<MAX_MOUSE_REPORT_LEN> {
+        use fmt::Write as _;
+
+        let mut report = heapless::String::new();
+        let motion = button & 32 != 0;
+        let wanted = match self.mouse_tracking {
+            MouseTracking::Off => false,
+            MouseTracking::Normal => !motion,
+            MouseTracking::ButtonEvent => true,
+        };
+        if !wanted {
+            return report;
+        }
+        if self.sgr_mouse {
+            let end = if pressed { 'M' } else { 'm' };
+            write!(report, "\x1b[<{button};{};{}{end}", x + 1, y + 1).ok();
+        } else {
+            // The legacy form has no way to say which button was
+            // released, and each value is one byte offset by 32. They
+            // are kept to ASCII so that the report is valid UTF-8.
+            let button = if pressed { button } else { 3 };
+            let encode = |n: usize| char::from(n.min(95) as u8 + 32);
+            write!(report, "\x1b[M{}{}{}", encode(button as usize), encode(x + 1), encode(y + 1)).ok();
+        }
+        report
+    }
+
+    /// Returns the input to send to the host to paste `text`,
+    /// bracketed if the program asked for that. An end marker inside
+    /// `text` is dropped so the paste can't end early.
+    pub fn encode_paste(&self, text: &str) -> String {
+        if !self.bracketed_paste {
+            return text.into();
+        }
+        let mut input = String::from("\x1b[200~");
+        input.push_str(&text.replace("\x1b[201~", ""));
+        input.push_str("\x1b[201~");
+        input
+    }
+
+    /// Returns the window title last set by the running program
+    pub fn title(&self) -> &str {
+        &self.title
+    }
+
+    /// Stores an OSC title, whose text vte splits on `;`
+    fn set_title(&mut self, parts: &[&[u8]]) {
+        self.title.clear();
+        for (i, part) in parts.iter().enumerate() {
+            let text = match core::str::from_utf8(part) {
+                Ok(text) => text,
+                Err(err) => core::str::from_utf8(&part[..err.valid_up_to()]).unwrap_or(""),
+            };
+            let separator = if i > 0 { Some(';') } else { None };
+            for c in separator.into_iter().chain(text.chars()) {
+                if c.is_control() {
+                    continue;
+                }
+                if self.title.push(c).is_err() {
+                    return;
+                }
+            }
+        }
+    }
+
+    /// Draws what changed since the last call, and returns the area
+    /// of the screen that was drawn over, or `None` if nothing was
+    pub fn update_display<D: TerminalDisplay>(&mut self, display: &mut D) -> Option<Rectangle>
+    where
+        D::Error: fmt::Debug,
+    {
+        // The visual bell inverts a single frame, margins and cursor
+        // included, and the frame after it repaints everything
+        // normally again
+        let flash = core::mem::take(&mut self.bell);
+        if flash != self.bell_shown {
+            self.bell_shown = flash;
+            self.full_repaint = true;
+        }
+        let display = &mut Invert {
+            target: display,
+            enabled: self.inverted ^ flash,
+        };
+
+        let reverse_screen = self.reverse_screen;
+        let palette = self.palette;
+        let correction = self.color_correction;
+        let resolve = |color: Color| match (reverse_screen, color) {
+            (true, Color::DefaultFg) => palette.bg,
+            (true, Color::DefaultBg) => palette.fg,
+            _ => color.to_rgb565(&palette, &correction),
+        };
+
+        let font = self.font;
         let cell_width = font.character_size.width + font.character_spacing;
-        let cell_height = font.character_size.height;
+        let cell_height = self.cell_height();
+        let glyph_height = font.character_size.height;
+        let text_height = self.rows as u32 * cell_height;
+        let (origin_x, origin_y) = self.grid_origin();
+        let (screen_width, screen_height) = self.screen_size();
+
+        // Move the panel's window onto frame memory down past the
+        // lines that scrolled off; what remains on screen is intact
+        // A grid taller than the panel can't be scrolled by it, nor
+        // can one whose rows run down the panel
+        let mut scrolled = core::mem::take(&mut self.pending_scroll);
+        if self.full_repaint
+            || scrolled >= self.rows
+            || text_height > screen_height
+            || (scrolled > 0 && (self.rotation.is_sideways() || !self.hardware_scroll))
+        {
+            self.full_repaint = true;
+            scrolled = 0;
+        }
+        if scrolled > 0 {
+            // Upside down, the lines move down the panel
+            let distance = match self.rotation {
+                Rotation::Deg180 => FRAME_HEIGHT as usize - scrolled * cell_height as usize,
+                _ => scrolled * cell_height as usize,
+            };
+            self.scroll_offset = (self.scroll_offset + distance) % FRAME_HEIGHT as usize;
+            display.set_scroll_offset(self.scroll_offset as u16);
+            self.prev_cursor = self
+                .prev_cursor
+                .and_then(|(x, y)| Some((x, y.checked_sub(scrolled)?)));
+        }
+        let display = &mut Damage {
+            target: &mut Rotated {
+                target: &mut Scrolled {
+                    target: display,
+                    offset: self.scroll_offset as i32,
+                    size: Size::new(self.panel_width as u32, self.panel_height as u32),
+                },
+                rotation: self.rotation,
+            },
+            // Scrolling moves everything on the panel
+            area: (scrolled > 0).then(|| {
+                Rectangle::new(Point::zero(), Size::new(screen_width, screen_height))
+            }),
+        };
+
+        let cols = self.cols;
+        if SHADOW_FRAMEBUFFER {
+            if self.full_repaint || self.shadow.len() != self.rows * cols {
+                self.shadow.clear();
+                self.shadow.resize(self.rows * cols, None);
+            } else if scrolled > 0 {
+                // The drawn cells moved up along with the scroll
+                self.shadow.rotate_left(scrolled * cols);
+                let len = self.shadow.len();
+                self.shadow[len - scrolled * cols..].fill(None);
+            }
+        }
+
+        if self.full_repaint {
+            display.clear(resolve(Color::DefaultBg)).unwrap();
+        } else if scrolled > 0 {
+            // Frame memory that scrolled into view is stale, including
+            // the margins beside and below the new lines
+            let top = origin_y + text_height - scrolled as u32 * cell_height;
+            display.fill_solid(
+                &Rectangle::new(
+                    Point::new(0, top as i32),
+                    Size::new(screen_width, screen_height - top),
+                ),
+                resolve(Color::DefaultBg),
+            ).unwrap();
+            // and the margin above the grid has lines scrolled into it
+            if origin_y > 0 {
+                display.fill_solid(
+                    &Rectangle::new(Point::zero(), Size::new(screen_width, origin_y)),
+                    resolve(Color::DefaultBg),
+                ).unwrap();
+            }
+        }
+
+        let blink_on = self.blink_on;
+        let view_start = self.view_start();
+        let mut drew_cells = self.full_repaint;
 
+        // In copy mode, the cursor shown is the selection cursor
+        let cursor = match &self.copy_mode {
+            Some(copy_mode) => copy_mode
+                .cursor
+                .line
+                .checked_sub(view_start)
+                .map(|y| (copy_mode.cursor.col, y)),
+            None if !self.cursor_visible => None,
+            None => Some((self.cursor_x, self.cursor_y)),
+        };
+        // An unchanged cursor is left alone unless what's under it is
+        // drawn over
+        let mut redraw_cursor = cursor != self.prev_cursor || self.full_repaint || scrolled > 0;
+        // Restore the cell the cursor has moved away from
+        if let Some((x, y)) = self.prev_cursor
+            && cursor != self.prev_cursor
+            && y < self.rows
+        {
+            self.abs_line_mut(view_start + y).touch(x, x + 1);
+        }
         for y in 0..self.rows {
-            let line_idx = if self.viewport_offset > 0 {
-                // Calculate absolute index in history + lines
-                // Total lines = scrollback.len() + lines.len() (which is rows)
-                // View start = Total lines - rows - viewport_offset
-                // Current row abs index = View start + y
-                let total_len = self.scrollback.len() + self.rows;
-                let view_start = total_len.saturating_sub(self.rows).saturating_sub(self.viewport_offset);
-                let abs_idx = view_start + y;
-                
-                if abs_idx < self.scrollback.len() {
-                    Some(&mut self.scrollback[abs_idx])
-                } else {
-                    Some(&mut self.lines[abs_idx - self.scrollback.len()])
-                }
+            let abs_idx = view_start + y;
+            let selected = self.selection_on_line(abs_idx);
+            let line = if abs_idx < self.scrollback.len() {
+                &mut self.scrollback[abs_idx]
             } else {
-                Some(&mut self.lines[y])
+                &mut self.lines[abs_idx - self.scrollback.len()]
             };
 
-            let line = match line_idx {
-                Some(l) => l,
-                None => continue,
+            let dirty = if self.full_repaint {
+                Some((0, line.chars.len()))
+            } else {
+                line.dirty
             };
-
-            if !line.dirty && !self.full_repaint {
+            let Some((start, end)) = dirty else {
                 continue;
-            }
+            };
             
-            let row_y = y as u32 * cell_height as u32;
-            if row_y >= SCREEN_HEIGHT as u32 { break; }
+            let row_y = origin_y + y as u32 * cell_height;
+            if row_y >= screen_height { break; }
+
+            // When the columns don't reach the right edge of the panel,
+            // what's left is painted along with the end of the line. On a
+            // double width line with an odd number of columns, that
+            // includes the last column.
+            let line_cols = line_cols(cols, line.size);
+            let scale = if line.size == LineSize::Single { 1 } else { 2 };
+            let line_end = origin_x + line_cols as u32 * cell_width * scale;
+            if !self.full_repaint && end >= line_cols && line_end < screen_width {
+                display.fill_solid(
+                    &Rectangle::new(
+                        Point::new(line_end as i32, row_y as i32),
+                        Size::new(screen_width - line_end, cell_height),
+                    ),
+                    resolve(Color::DefaultBg),
+                ).unwrap();
+                // Which may have been over the scrollbar
+                drew_cells = true;
+            }
 
-            for (x, (char, attr)) in line.chars.iter().zip(line.attrs.iter()).enumerate() {
-                let col_x = x as u32 * cell_width;
-                if col_x >= SCREEN_WIDTH as u32 { break; }
+            // Cells of double size lines are laid out as if at normal
+            // size and scaled on their way to the panel
+            let display = &mut LineScale {
+                target: &mut *display,
+                size: line.size,
+                left: origin_x as i32,
+                top: row_y as i32,
+                height: cell_height as i32,
+            };
+
+            // Redrawing the right half of a wide character means
+            // redrawing the whole character
+            let start = if start > 0 && line.chars.get(start) == Some(&WIDE_CONTINUATION) {
+                start - 1
+            } else {
+                start
+            };
+            // The cells to draw, as (x, span, fg, bg); no more than
+            // fit across the panel
+            let mut to_draw = heapless::Vec::<_, MAX_DRAWN_CELLS>::new();
+            // Scrollback lines keep the width they were written at,
+            // which may be wider than the grid is now
+            let cells = line.chars.iter().zip(line.attrs.iter()).enumerate();
+            for (x, (char, attr)) in cells.take(end.min(line_cols)).skip(start) {
+                if *char == WIDE_CONTINUATION {
+                    continue;
+                }
+                let col_x = origin_x + x as u32 * cell_width;
+                if col_x >= screen_width || to_draw.is_full() { break; }
+                let span = if line.chars.get(x + 1) == Some(&WIDE_CONTINUATION) { 2 } else { 1 };
 
-                let mut fg = attr.fg.to_rgb565(false);
-                let mut bg = attr.bg.to_rgb565(true);
+                let mut fg = resolve(attr.fg());
+                let mut bg = resolve(attr.bg());
                 
-                if attr.reverse {
+                let in_selection =
+                    matches!(selected, Some((first, last)) if x >= first && x <= last);
+                if attr.reverse() ^ in_selection {
                     core::mem::swap(&mut fg, &mut bg);
                 }
                 
-                if attr.bold {
+                if attr.bold() {
                     // Brighten fg?
-                    if fg == Rgb565::CSS_LIGHT_GRAY { fg = Rgb565::WHITE; }
+                    if fg == palette.fg { fg = palette.ansi[15]; }
+                }
+
+                if attr.faint() {
+                    fg = Rgb565::new(fg.r() / 2, fg.g() / 2, fg.b() / 2);
+                }
+
+                if attr.conceal() || (attr.blink() && !blink_on) {
+                    fg = bg;
+                }
+
+                if SHADOW_FRAMEBUFFER {
+                    let drawn = Some(DrawnCell {
+                        c: *char,
+                        fg,
+                        bg,
+                        bold: attr.bold(),
+                        italic: attr.italic(),
+                        underline: attr
+                            .drawn_underline()
+                            .map(|style| (style, attr.underline_color().map_or(fg, resolve))),
+                        strikethrough: attr.strikethrough(),
+                        wide: span == 2,
+                        size: line.size,
+                    });
+                    let idx = y * cols + x;
+                    if self.shadow.get(idx) == Some(&drawn) {
+                        continue;
+                    }
+                    self.shadow[idx] = drawn;
+                    if span == 2
+                        && let Some(right) = self.shadow.get_mut(idx + 1)
+                    {
+                        *right = None;
+                    }
+                }
+                redraw_cursor |= cursor == Some((x, y)) || (span == 2 && cursor == Some((x + 1, y)));
+                to_draw.push((x, span, fg, bg)).ok();
+            }
+            drew_cells |= !to_draw.is_empty();
+            // The cells past the end of a double width line aren't drawn
+            // and must be again if it becomes single width
+            if SHADOW_FRAMEBUFFER && line_cols < cols {
+                let row = y * cols;
+                if let Some(hidden) = self.shadow.get_mut(row + line_cols..row + cols) {
+                    hidden.fill(None);
                 }
+            }
 
-                // Draw background
+            // Fill the background of each run of adjacent cells that
+            // share a color at once, which for a mostly blank line is
+            // far fewer fills than one per cell
+            let mut i = 0;
+            while i < to_draw.len() {
+                let (run_x, _, _, bg) = to_draw[i];
+                let mut end_x = run_x;
+                while let Some(&(x, span, _, cell_bg)) = to_draw.get(i) {
+                    if x != end_x || cell_bg != bg {
+                        break;
+                    }
+                    end_x += span;
+                    i += 1;
+                }
                 display.fill_solid(
                     &Rectangle::new(
-                        Point::new(col_x as i32, row_y as i32),
-                        Size::new(cell_width, cell_height as u32),
+                        Point::new((origin_x + run_x as u32 * cell_width) as i32, row_y as i32),
+                        Size::new((end_x - run_x) as u32 * cell_width, cell_height),
                     ),
                     bg,
                 ).unwrap();
+            }
+
+            for &(x, span, fg, bg) in &to_draw {
+                let (char, attr) = (&line.chars[x], &line.attrs[x]);
+                let col_x = origin_x + x as u32 * cell_width;
+                let glyph_width = cell_width * span as u32;
+                // Center the glyph of a wide character over its cells
+                let text_x = col_x + (glyph_width - cell_width) / 2;
 
                 // Draw text
                 if *char != ' ' {
@@ -414,122 +2923,363 @@ impl ScreenModel {
                     let mut buf = [0u8; 4];
                     let s = char.encode_utf8(&mut buf);
 
-                    // Check for box drawing characters (U+2500 - U+259F)
-                    if ('\u{2500}'..='\u{259F}').contains(char) {
-                        draw_box_char(display, *char, col_x as i32, row_y as i32, cell_width, cell_height as u32, fg);
+                    if is_drawn_glyph(*char) {
+                        draw_box_char(display, *char, col_x as i32, row_y as i32, cell_width, cell_height, fg);
+                    } else if attr.italic() {
+                        // The background is already filled, and must not
+                        // be slanted into the neighboring cells
+                        let style = MonoTextStyleBuilder::new()
+                            .font(font)
+                            .text_color(fg)
+                            .build();
+                        let mut slanted = Slanted {
+                            target: &mut *display,
+                            center_y: row_y as i32 + glyph_height as i32 / 2,
+                        };
+                        for dx in 0..=attr.bold() as i32 {
+                            Text::new(
+                                s,
+                                Point::new(text_x as i32 + dx, row_y as i32 + font.baseline as i32),
+                                style,
+                            )
+                            .draw(&mut slanted)
+                            .ok();
+                        }
                     } else {
                         Text::new(
                             s,
-                            Point::new(col_x as i32, (row_y as i32 + font.baseline as i32)),
+                            Point::new(text_x as i32, row_y as i32 + font.baseline as i32),
                             style,
                         )
                         .draw(display)
                         .ok(); // Ignore errors for missing glyphs
+
+                        if attr.bold() {
+                            // Faux bold: strike the glyph again one pixel
+                            // to the right, without a background
+                            let style = MonoTextStyleBuilder::new()
+                                .font(font)
+                                .text_color(fg)
+                                .build();
+                            Text::new(
+                                s,
+                                Point::new(text_x as i32 + 1, row_y as i32 + font.baseline as i32),
+                                style,
+                            )
+                            .draw(display)
+                            .ok();
+                        }
                     }
                 }
                 
-                if attr.underline {
-                     display.fill_solid(
+                if let Some(style) = attr.drawn_underline() {
+                    draw_underline(
+                        display,
+                        style,
+                        col_x as i32,
+                        (row_y + glyph_height - 1) as i32,
+                        glyph_width,
+                        attr.underline_color().map_or(fg, resolve),
+                    );
+                }
+
+                if attr.strikethrough() {
+                    display.fill_solid(
                         &Rectangle::new(
-                            Point::new(col_x as i32, (row_y + cell_height as u32 - 1) as i32),
-                            Size::new(cell_width, 1),
+                            Point::new(col_x as i32, (row_y + glyph_height / 2) as i32),
+                            Size::new(glyph_width, 1),
                         ),
                         fg,
                     ).unwrap();
                 }
             }
-            line.dirty = false;
+            line.dirty = None;
+        }
+
+        // While looking back through scrollback, a scrollbar along the
+        // right edge shows where the view is. Redrawn cells may have
+        // covered it, and a full repaint without it clears it away.
+        // Its thumb is highlighted when scroll lock is holding back
+        // output below.
+        if self.viewport_offset == 0 {
+            self.output_held = false;
+        }
+        if self.viewport_offset > 0 && drew_cells {
+            let total = (self.scrollback.len() + self.rows) as u32;
+            let thumb_top = origin_y + view_start as u32 * text_height / total;
+            let thumb_height = (self.rows as u32 * text_height / total).max(4);
+            display.fill_solid(
+                &Rectangle::new(
+                    Point::new((screen_width - SCROLLBAR_WIDTH) as i32, origin_y as i32),
+                    Size::new(SCROLLBAR_WIDTH, text_height),
+                ),
+                resolve(Color::DefaultBg),
+            ).unwrap();
+            display.fill_solid(
+                &Rectangle::new(
+                    Point::new((screen_width - SCROLLBAR_WIDTH) as i32, thumb_top as i32),
+                    Size::new(SCROLLBAR_WIDTH, thumb_height),
+                ),
+                if self.output_held {
+                    resolve(Color::BrightYellow)
+                } else {
+                    resolve(Color::DefaultFg)
+                },
+            ).unwrap();
+        }
+
+        // The status bar sits below the grid, so it is drawn over
+        // whenever the grid is cleared or scrolled
+        if self.status_bar && (self.full_repaint || scrolled > 0 || self.status_dirty) {
+            self.status_dirty = false;
+            let (fg, bg) = (resolve(Color::DefaultBg), resolve(Color::DefaultFg));
+            display.fill_solid(
+                &Rectangle::new(
+                    Point::new(0, (origin_y + text_height) as i32),
+                    Size::new(screen_width, cell_height),
+                ),
+                bg,
+            ).unwrap();
+            let end = self
+                .status_text
+                .char_indices()
+                .nth(cols)
+                .map_or(self.status_text.len(), |(i, _)| i);
+            let style = MonoTextStyleBuilder::new()
+                .font(font)
+                .text_color(fg)
+                .build();
+            Text::new(
+                &self.status_text[..end],
+                Point::new(origin_x as i32, (origin_y + text_height) as i32 + font.baseline as i32),
+                style,
+            )
+            .draw(display)
+            .ok();
         }
         self.full_repaint = false;
 
         // Draw cursor
-        let cx = self.cursor_x as u32 * cell_width;
-        let cy = self.cursor_y as u32 * cell_height as u32;
-        if cx < SCREEN_WIDTH as u32 && cy < SCREEN_HEIGHT as u32 {
+        self.prev_cursor = cursor;
+        let Some((cursor_x, cursor_y)) = cursor else {
+            return display.area;
+        };
+        if !redraw_cursor {
+            return display.area;
+        }
+        // The cursor covers whatever the shadow says is in its cell
+        if let Some(drawn) = self.shadow.get_mut(cursor_y * cols + cursor_x) {
+            *drawn = None;
+        }
+        let size = (view_start + cursor_y < self.total_lines())
+            .then(|| self.abs_line(view_start + cursor_y).size);
+        let scale = match size {
+            Some(LineSize::Single) | None => 1,
+            Some(_) => 2,
+        };
+        let cell_width = cell_width * scale;
+        let cx = origin_x + cursor_x as u32 * cell_width;
+        let cy = origin_y + cursor_y as u32 * cell_height;
+        // The copy mode cursor is always a block
+        let shape = match self.copy_mode {
+            Some(_) => CursorShape::Block,
+            None => self.cursor_shape,
+        };
+        let (x, y, width, height) = match shape {
+            CursorShape::Block => (cx, cy, cell_width, cell_height),
+            CursorShape::Underline => (cx, cy + cell_height - 2, cell_width, 2),
+            CursorShape::Bar => (cx, cy, 2, cell_height),
+        };
+        if cx < screen_width && cy < screen_height {
              display.fill_solid(
                 &Rectangle::new(
-                    Point::new(cx as i32, cy as i32),
-                    Size::new(cell_width, cell_height as u32),
+                    Point::new(x as i32, y as i32),
+                    Size::new(width, height),
                 ),
-                Rgb565::WHITE, 
+                palette.cursor,
             ).ok();
         }
+        display.area
     }
 }
 
 impl vte::Perform for ScreenModel {
     fn print(&mut self, c: char) {
         self.reset_view();
+        // Combining marks, variation selectors and joiners take no
+        // cell of their own; only the base character is drawn
+        if c.width() == Some(0) {
+            return;
+        }
+        let c = self.charset.translate(c);
+        self.last_char = Some(c);
         if self.cursor_y >= self.rows {
             self.scroll_up();
-            self.cursor_y = self.rows - 1;
+            self.cursor_y = self.rows.saturating_sub(1);
         }
-        if self.cursor_x >= self.cols {
+        if self.wrap_pending && self.autowrap {
             self.cursor_x = 0;
-            self.cursor_y += 1;
-            if self.cursor_y >= self.rows {
-                self.scroll_up();
-                self.cursor_y = self.rows - 1;
+            self.line_feed();
+        }
+        self.wrap_pending = false;
+        self.cursor_x = self.cursor_x.min(self.cursor_line_cols().saturating_sub(1));
+
+        let width = if c.width() == Some(2) && self.cursor_line_cols() >= 2 { 2 } else { 1 };
+        // A wide character never straddles two rows
+        if self.cursor_x + width > self.cursor_line_cols() {
+            if self.autowrap {
+                self.cursor_x = 0;
+                self.line_feed();
+            } else {
+                self.cursor_x = self.cursor_line_cols().saturating_sub(width);
             }
         }
         
+        let cols = self.cursor_line_cols();
+        let x = self.cursor_x;
         let line = &mut self.lines[self.cursor_y];
-        if self.cursor_x < line.chars.len() {
-            line.chars[self.cursor_x] = c;
-            line.attrs[self.cursor_x] = self.current_attrs;
-            line.dirty = true;
-            self.cursor_x += 1;
+        if x + width <= line.chars.len() {
+            // Don't leave half of a wide character behind
+            if line.chars[x] == WIDE_CONTINUATION && x > 0 {
+                line.chars[x - 1] = ' ';
+                line.touch(x - 1, x);
+            }
+            if line.chars.get(x + width) == Some(&WIDE_CONTINUATION) {
+                line.chars[x + width] = ' ';
+                line.touch(x + width, x + width + 1);
+            }
+            line.chars[x] = c;
+            line.attrs[x] = self.current_attrs;
+            if width == 2 {
+                line.chars[x + 1] = WIDE_CONTINUATION;
+                line.attrs[x + 1] = self.current_attrs;
+            }
+            line.touch(x, x + width);
+            if x + width < cols {
+                self.cursor_x = x + width;
+            } else {
+                self.cursor_x = cols.saturating_sub(1);
+                self.wrap_pending = true;
+            }
         }
     }
 
     fn execute(&mut self, byte: u8) {
         self.reset_view();
+        if byte != b'\x07' {
+            self.wrap_pending = false;
+        }
         match byte {
-            b'\n' => { // LF
-                self.cursor_y += 1;
-                if self.cursor_y >= self.rows {
-                    self.scroll_up();
-                    self.cursor_y = self.rows - 1;
-                }
+            b'\n' | b'\x0b' | b'\x0c' => { // LF, VT, FF
+                self.line_feed();
             }
             b'\r' => { // CR
                 self.cursor_x = 0;
             }
-            b'\x08' => { // BS
-                if self.cursor_x > 0 {
-                    self.cursor_x -= 1;
-                }
+            b'\t' => { // HT
+                self.cursor_x = (self.cursor_x + 1..self.cols)
+                    .find(|&x| self.tab_stops[x])
+                    .unwrap_or(self.cols.saturating_sub(1));
+            }
+            b'\x08' if self.cursor_x > 0 => { // BS
+                self.cursor_x -= 1;
+            }
+            b'\x07' => { // BEL
+                self.bell = true;
             }
             _ => {}
         }
     }
 
     fn csi_dispatch(&mut self, params: &vte::Params, intermediates: &[u8], ignore: bool, action: char) {
-        if ignore || !intermediates.is_empty() { return; }
+        if ignore { return; }
+
+        match intermediates {
+            [] => {}
+            [b' '] => {
+                if action == 'q' { // DECSCUSR
+                    let ps = params.iter().next().map(|p| p[0]).unwrap_or(0);
+                    self.set_cursor_shape(ps);
+                }
+                return;
+            }
+            [b'?'] => {
+                let ps = params.iter().next().map(|p| p[0]).unwrap_or(0);
+                match action {
+                    'h' => self.set_dec_modes(params, true),
+                    'l' => self.set_dec_modes(params, false),
+                    'J' => self.selective_erase_display(ps), // DECSED
+                    'K' => self.selective_erase_line(ps), // DECSEL
+                    _ => {}
+                }
+                return;
+            }
+            [b'"'] => {
+                if action == 'q' { // DECSCA
+                    match params.iter().next().map(|p| p[0]).unwrap_or(0) {
+                        1 => self.current_attrs.flags.insert(AttrFlags::PROTECTED),
+                        0 | 2 => self.current_attrs.flags.remove(AttrFlags::PROTECTED),
+                        _ => {}
+                    }
+                }
+                return;
+            }
+            [b'!'] => {
+                if action == 'p' {
+                    self.soft_reset();
+                }
+                return;
+            }
+            _ => return,
+        }
+
+        // Everything but SGR, queries and REP moves or edits around the
+        // cursor
+        if !matches!(action, 'm' | 'n' | 'c' | 'b' | 't') {
+            self.wrap_pending = false;
+        }
 
         match action {
             'A' => { // Cursor Up
-                let n = params.iter().next().map(|p| p[0]).unwrap_or(1) as usize;
+                let n = params.iter().next().map(|p| p[0]).unwrap_or(1).max(1) as usize;
                 self.cursor_y = self.cursor_y.saturating_sub(n);
             }
             'B' => { // Cursor Down
-                let n = params.iter().next().map(|p| p[0]).unwrap_or(1) as usize;
-                self.cursor_y = (self.cursor_y + n).min(self.rows - 1);
+                let n = params.iter().next().map(|p| p[0]).unwrap_or(1).max(1) as usize;
+                self.cursor_y = (self.cursor_y + n).min(self.rows.saturating_sub(1));
             }
             'C' => { // Cursor Forward
-                let n = params.iter().next().map(|p| p[0]).unwrap_or(1) as usize;
-                self.cursor_x = (self.cursor_x + n).min(self.cols - 1);
+                let n = params.iter().next().map(|p| p[0]).unwrap_or(1).max(1) as usize;
+                self.cursor_x = (self.cursor_x + n).min(self.cursor_line_cols().saturating_sub(1));
             }
             'D' => { // Cursor Backward
-                let n = params.iter().next().map(|p| p[0]).unwrap_or(1) as usize;
+                let n = params.iter().next().map(|p| p[0]).unwrap_or(1).max(1) as usize;
                 self.cursor_x = self.cursor_x.saturating_sub(n);
             }
+            'E' => { // Cursor Next Line
+                let n = params.iter().next().map(|p| p[0]).unwrap_or(1).max(1) as usize;
+                self.cursor_y = (self.cursor_y + n).min(self.rows.saturating_sub(1));
+                self.cursor_x = 0;
+            }
+            'F' => { // Cursor Previous Line
+                let n = params.iter().next().map(|p| p[0]).unwrap_or(1).max(1) as usize;
+                self.cursor_y = self.cursor_y.saturating_sub(n);
+                self.cursor_x = 0;
+            }
+            'G' => { // Cursor Horizontal Absolute
+                let col = params.iter().next().map(|p| p[0]).unwrap_or(1).max(1) as usize - 1;
+                self.cursor_x = col.min(self.cursor_line_cols().saturating_sub(1));
+            }
+            'd' => { // Vertical Position Absolute
+                let row = params.iter().next().map(|p| p[0]).unwrap_or(1).max(1) as usize - 1;
+                self.set_cursor_row(row);
+            }
             'H' | 'f' => { // Cursor Position
                 let mut iter = params.iter();
                 let row = iter.next().map(|p| p[0]).unwrap_or(1).max(1) as usize - 1;
                 let col = iter.next().map(|p| p[0]).unwrap_or(1).max(1) as usize - 1;
-                self.cursor_y = row.min(self.rows - 1);
-                self.cursor_x = col.min(self.cols - 1);
+                self.set_cursor_row(row);
+                self.cursor_x = col.min(self.cursor_line_cols().saturating_sub(1));
             }
             'J' => { // Erase in Display
                 let n = params.iter().next().map(|p| p[0]).unwrap_or(0);
@@ -541,7 +3291,7 @@ impl vte::Perform for ScreenModel {
                             line.chars[i] = ' ';
                             line.attrs[i] = self.current_attrs;
                         }
-                        line.dirty = true;
+                        line.touch(self.cursor_x, self.cols);
                         // Clear lines below
                         for i in (self.cursor_y + 1)..self.rows {
                             self.lines[i].clear();
@@ -558,57 +3308,217 @@ impl vte::Perform for ScreenModel {
                             line.chars[i] = ' ';
                             line.attrs[i] = self.current_attrs;
                         }
-                        line.dirty = true;
+                        line.touch(0, self.cursor_x + 1);
                     }
                     2 => { // Entire screen
                         self.clear();
                     }
+                    3 => { // Scrollback
+                        self.clear_scrollback();
+                    }
+                    _ => {}
+                }
+            }
+            'K' => { // Erase in Line
+                let n = params.iter().next().map(|p| p[0]).unwrap_or(0);
+                let (start, end) = match n {
+                    0 => (self.cursor_x, self.cols), // Cursor to end
+                    1 => (0, self.cursor_x + 1), // Beginning to cursor
+                    2 => (0, self.cols), // Entire line
+                    _ => return,
+                };
+                let line = &mut self.lines[self.cursor_y];
+                for i in start..end {
+                    line.chars[i] = ' ';
+                    line.attrs[i] = self.current_attrs;
+                }
+                line.touch(start, end);
+            }
+            'c' if params.iter().next().map(|p| p[0]).unwrap_or(0) == 0 => { // Device Attributes
+                // VT102
+                self.reply(format_args!("\x1b[?6c"));
+            }
+            'n' => { // Device Status Report
+                match params.iter().next().map(|p| p[0]).unwrap_or(0) {
+                    5 => self.reply(format_args!("\x1b[0n")),
+                    6 => {
+                        let row = if self.origin_mode {
+                            self.cursor_y.saturating_sub(self.scroll_top)
+                        } else {
+                            self.cursor_y
+                        };
+                        self.reply(format_args!("\x1b[{};{}R", row + 1, self.cursor_x + 1));
+                    }
+                    _ => {}
+                }
+            }
+            't' => { // Window manipulation; only the size reports
+                match params.iter().next().map(|p| p[0]).unwrap_or(0) {
+                    14 => {
+                        let width = self.cols as u32
+                            * (self.font.character_size.width + self.font.character_spacing);
+                        let height = self.rows as u32 * self.cell_height();
+                        self.reply(format_args!("\x1b[4;{};{}t", height, width));
+                    }
+                    18 => {
+                        let (rows, cols) = (self.rows, self.cols);
+                        self.reply(format_args!("\x1b[8;{};{}t", rows, cols));
+                    }
                     _ => {}
                 }
             }
-            'K' => { // Erase in Line
-                let n = params.iter().next().map(|p| p[0]).unwrap_or(0);
+            'S' => { // Scroll Up
+                let n = params.iter().next().map(|p| p[0]).unwrap_or(1).max(1) as usize;
+                for _ in 0..n.min((self.scroll_bottom + 1).saturating_sub(self.scroll_top)) {
+                    self.scroll_up();
+                }
+            }
+            'T' => { // Scroll Down
+                let n = params.iter().next().map(|p| p[0]).unwrap_or(1).max(1) as usize;
+                self.scroll_down(n);
+            }
+            'L' => { // Insert Line
+                let n = params.iter().next().map(|p| p[0]).unwrap_or(1).max(1) as usize;
+                self.insert_lines(n);
+            }
+            'M' => { // Delete Line
+                let n = params.iter().next().map(|p| p[0]).unwrap_or(1).max(1) as usize;
+                self.delete_lines(n);
+            }
+            'b' => { // Repeat last character
+                let Some(c) = self.last_char else { return };
+                let n = params.iter().next().map(|p| p[0]).unwrap_or(1).max(1) as usize;
+                // Anything past a screenful would just be scrolled away
+                for _ in 0..n.min(self.rows * self.cols) {
+                    vte::Perform::print(self, c);
+                }
+            }
+            '@' => { // Insert Character
+                let n = params.iter().next().map(|p| p[0]).unwrap_or(1).max(1) as usize;
+                let x = self.cursor_x.min(self.cols.saturating_sub(1));
+                let n = n.min(self.cols - x);
+                let blank = self.current_attrs;
+                let line = &mut self.lines[self.cursor_y];
+                line.chars[x..].rotate_right(n);
+                line.attrs[x..].rotate_right(n);
+                for i in x..x + n {
+                    line.chars[i] = ' ';
+                    line.attrs[i] = blank;
+                }
+                line.touch(x, self.cols);
+            }
+            'P' => { // Delete Character
+                let n = params.iter().next().map(|p| p[0]).unwrap_or(1).max(1) as usize;
+                let x = self.cursor_x.min(self.cols.saturating_sub(1));
+                let n = n.min(self.cols - x);
+                let blank = self.current_attrs;
+                let line = &mut self.lines[self.cursor_y];
+                line.chars[x..].rotate_left(n);
+                line.attrs[x..].rotate_left(n);
+                for i in self.cols - n..self.cols {
+                    line.chars[i] = ' ';
+                    line.attrs[i] = blank;
+                }
+                line.touch(x, self.cols);
+            }
+            'X' => { // Erase Character
+                let n = params.iter().next().map(|p| p[0]).unwrap_or(1).max(1) as usize;
+                let x = self.cursor_x.min(self.cols.saturating_sub(1));
+                let n = n.min(self.cols - x);
                 let line = &mut self.lines[self.cursor_y];
-                match n {
-                    0 => { // Cursor to end
-                        for i in self.cursor_x..self.cols {
-                            line.chars[i] = ' ';
-                            line.attrs[i] = self.current_attrs;
-                        }
-                    }
-                    1 => { // Beginning to cursor
-                        for i in 0..=self.cursor_x {
-                            line.chars[i] = ' ';
-                            line.attrs[i] = self.current_attrs;
-                        }
-                    }
-                    2 => { // Entire line
-                        for i in 0..self.cols {
-                            line.chars[i] = ' ';
-                            line.attrs[i] = self.current_attrs;
+                for i in x..x + n {
+                    line.chars[i] = ' ';
+                    line.attrs[i] = self.current_attrs;
+                }
+                line.touch(x, x + n);
+            }
+            'g' => { // Tab Clear
+                match params.iter().next().map(|p| p[0]).unwrap_or(0) {
+                    0 => {
+                        if let Some(stop) = self.tab_stops.get_mut(self.cursor_x) {
+                            *stop = false;
                         }
                     }
+                    3 => self.tab_stops.fill(false),
                     _ => {}
                 }
-                line.dirty = true;
+            }
+            'r' => { // Set Scroll Region
+                self.set_scroll_region(params);
+            }
+            // When left/right margin mode (DECLRMM) is enabled, xterm
+            // treats CSI s as DECSLRM (set left and right margins).
+            // We don't support margins, so this is always ANSI.SYS save.
+            's' => { // Save Cursor
+                self.save_cursor();
+            }
+            'u' => { // Restore Cursor
+                self.restore_cursor();
             }
             'm' => { // SGR
-                for param in params.iter() {
+                let mut iter = params.iter();
+                while let Some(param) = iter.next() {
                     let p = param[0];
                     match p {
-                        0 => self.current_attrs = Attrs::default(),
-                        1 => self.current_attrs.bold = true,
-                        4 => self.current_attrs.underline = true,
-                        7 => self.current_attrs.reverse = true,
-                        22 => self.current_attrs.bold = false,
-                        24 => self.current_attrs.underline = false,
-                        27 => self.current_attrs.reverse = false,
-                        30..=37 => self.current_attrs.fg = Color::Indexed((p - 30) as u8),
-                        39 => self.current_attrs.fg = Color::DefaultFg,
-                        40..=47 => self.current_attrs.bg = Color::Indexed((p - 40) as u8),
-                        49 => self.current_attrs.bg = Color::DefaultBg,
-                        90..=97 => self.current_attrs.fg = Color::Indexed((p - 90 + 8) as u8),
-                        100..=107 => self.current_attrs.bg = Color::Indexed((p - 100 + 8) as u8),
+                        // A hyperlink lasts until OSC 8 ends it, and
+                        // protection until DECSCA does
+                        0 => self.current_attrs = Attrs {
+                            link: self.current_attrs.link,
+                            flags: self.current_attrs.flags & AttrFlags::PROTECTED,
+                            ..Attrs::default()
+                        },
+                        1 => self.current_attrs.flags.insert(AttrFlags::BOLD),
+                        2 => self.current_attrs.flags.insert(AttrFlags::FAINT),
+                        3 => self.current_attrs.flags.insert(AttrFlags::ITALIC),
+                        4 => {
+                            self.current_attrs.underline = match param.get(1) {
+                                None | Some(1) => UnderlineStyle::Single,
+                                Some(2) => UnderlineStyle::Double,
+                                Some(3) => UnderlineStyle::Curly,
+                                Some(4) => UnderlineStyle::Dotted,
+                                Some(5) => UnderlineStyle::Dashed,
+                                _ => UnderlineStyle::None,
+                            };
+                        }
+                        5 | 6 => self.current_attrs.flags.insert(AttrFlags::BLINK),
+                        7 => self.current_attrs.flags.insert(AttrFlags::REVERSE),
+                        8 => self.current_attrs.flags.insert(AttrFlags::CONCEAL),
+                        9 => self.current_attrs.flags.insert(AttrFlags::STRIKETHROUGH),
+                        // Double underline, not the "bold off" of some
+                        // older terminals
+                        21 => self.current_attrs.underline = UnderlineStyle::Double,
+                        22 => {
+                            self.current_attrs.flags.remove(AttrFlags::BOLD);
+                            self.current_attrs.flags.remove(AttrFlags::FAINT);
+                        }
+                        23 => self.current_attrs.flags.remove(AttrFlags::ITALIC),
+                        24 => self.current_attrs.underline = UnderlineStyle::None,
+                        25 => self.current_attrs.flags.remove(AttrFlags::BLINK),
+                        27 => self.current_attrs.flags.remove(AttrFlags::REVERSE),
+                        28 => self.current_attrs.flags.remove(AttrFlags::CONCEAL),
+                        29 => self.current_attrs.flags.remove(AttrFlags::STRIKETHROUGH),
+                        30..=37 => self.current_attrs.set_fg(Color::Indexed((p - 30) as u8)),
+                        38 => {
+                            if let Some(color) = parse_sgr_color(param, &mut iter) {
+                                self.current_attrs.set_fg(color);
+                            }
+                        }
+                        39 => self.current_attrs.set_fg(Color::DefaultFg),
+                        40..=47 => self.current_attrs.set_bg(Color::Indexed((p - 40) as u8)),
+                        48 => {
+                            if let Some(color) = parse_sgr_color(param, &mut iter) {
+                                self.current_attrs.set_bg(color);
+                            }
+                        }
+                        49 => self.current_attrs.set_bg(Color::DefaultBg),
+                        58 => {
+                            if let Some(color) = parse_sgr_color(param, &mut iter) {
+                                self.current_attrs.set_underline_color(Some(color));
+                            }
+                        }
+                        59 => self.current_attrs.set_underline_color(None),
+                        90..=97 => self.current_attrs.set_fg(Color::Indexed((p - 90 + 8) as u8)),
+                        100..=107 => self.current_attrs.set_bg(Color::Indexed((p - 100 + 8) as u8)),
                         _ => {}
                     }
                 }
@@ -620,21 +3530,86 @@ impl vte::Perform for ScreenModel {
     fn hook(&mut self, _params: &vte::Params, _intermediates: &[u8], _ignore: bool, _action: char) {}
     fn put(&mut self, _byte: u8) {}
     fn unhook(&mut self) {}
-    fn osc_dispatch(&mut self, _params: &[&[u8]], _bell_terminated: bool) {}
-    fn esc_dispatch(&mut self, _intermediates: &[u8], _ignore: bool, _byte: u8) {}
+    fn osc_dispatch(&mut self, params: &[&[u8]], bell_terminated: bool) {
+        match params {
+            [b"0" | b"2", title @ ..] => self.set_title(title),
+            [b"4", pairs @ ..] => self.set_palette_colors(pairs),
+            [b"8", _params, uri @ ..] => self.set_hyperlink(uri),
+            [b"10", specs @ ..] => self.set_dynamic_colors(10, specs, bell_terminated),
+            [b"11", specs @ ..] => self.set_dynamic_colors(11, specs, bell_terminated),
+            [b"12", specs @ ..] => self.set_dynamic_colors(12, specs, bell_terminated),
+            _ => {}
+        }
+    }
+    fn esc_dispatch(&mut self, intermediates: &[u8], ignore: bool, byte: u8) {
+        if ignore { return; }
+
+        match (intermediates, byte) {
+            ([], b'7') => self.save_cursor(), // DECSC
+            ([], b'8') => self.restore_cursor(), // DECRC
+            ([], b'c') => self.reset(), // RIS
+            ([b'#'], b'3') => self.set_line_size(LineSize::DoubleHeightTop), // DECDHL
+            ([b'#'], b'4') => self.set_line_size(LineSize::DoubleHeightBottom), // DECDHL
+            ([b'#'], b'5') => self.set_line_size(LineSize::Single), // DECSWL
+            ([b'#'], b'6') => self.set_line_size(LineSize::DoubleWidth), // DECDWL
+            ([b'#'], b'8') => self.screen_alignment(), // DECALN
+            ([b'('], b'0') => self.charset = Charset::DecGraphics,
+            ([b'('], b'B') => self.charset = Charset::Ascii,
+            ([], b'=') => self.app_keypad = true, // DECKPAM
+            ([], b'>') => self.app_keypad = false, // DECKPNM
+            ([], b'D') => { // IND
+                self.wrap_pending = false;
+                self.line_feed();
+            }
+            ([], b'E') => { // NEL
+                self.wrap_pending = false;
+                self.cursor_x = 0;
+                self.line_feed();
+            }
+            ([], b'M') => { // RI
+                self.wrap_pending = false;
+                self.reverse_index();
+            }
+            ([], b'H') => { // HTS
+                if let Some(stop) = self.tab_stops.get_mut(self.cursor_x) {
+                    *stop = true;
+                }
+            }
+            _ => {}
+        }
+    }
 }
 
+#[cfg(target_os = "none")]
 #[embassy_executor::task]
 pub async fn screen_painter(mut display: PicoCalcDisplay<'static>) {
     display.clear(Rgb565::BLACK).unwrap();
-    if let Err(err) = display.set_vertical_scroll_region(0, 0) {
-        // log::error!("failed to set_vertical_scroll_region: {err:?}");
+    // The screen scrolls through a ring of FRAME_HEIGHT rows of frame
+    // memory, and whatever of it is left over stays fixed below
+    let rows = <ILI9488Rgb565 as Model>::FRAMEBUFFER_SIZE.1;
+    let (top, scroll) = (0, FRAME_HEIGHT as u16);
+    let bottom = rows - top - scroll;
+    if let Err(err) = display.set_vertical_scroll_region(top, bottom) {
+        log::error!("failed to set the vertical scroll region to {top}/{scroll}/{bottom}: {err:?}");
+        SCREEN.get().lock().await.set_hardware_scroll(false);
     }
 
-    let mut ticker = Ticker::every(Duration::from_millis(200));
+    let mut last_blink = Instant::now();
     loop {
-        SCREEN.get().lock().await.update_display(&mut display);
-        ticker.next().await;
+        {
+            let mut screen = SCREEN.get().lock().await;
+            if last_blink.elapsed() >= BLINK_INTERVAL {
+                last_blink = Instant::now();
+                screen.toggle_blink();
+            }
+            screen.update_display(&mut display);
+            // Everything changed until now has been drawn
+            REPAINT.reset();
+        }
+        // Draw at most once per refresh interval, and beyond that sleep
+        // until the screen changes or blinking text is due to toggle
+        Timer::after(refresh_interval()).await;
+        with_deadline(last_blink + BLINK_INTERVAL, REPAINT.wait()).await.ok();
     }
 }
 
@@ -642,8 +3617,493 @@ pub async fn cls_command(_args: &[&str]) {
     SCREEN.get().lock().await.clear();
 }
 
-fn draw_box_char(
-    display: &mut PicoCalcDisplay,
+pub async fn echo_command(args: &[&str]) {
+    let text = args.get(1..).unwrap_or_default().join(" ");
+    let mut screen = SCREEN.get().lock().await;
+    screen.print(&text);
+    screen.print("\r\n");
+}
+
+/// Resets the terminal as RIS (`ESC c`) does, for when a program
+/// left it in a strange state
+pub async fn reset_command(_args: &[&str]) {
+    SCREEN.get().lock().await.print("\x1bc");
+}
+
+/// Rows of frame memory in the panel. Hardware scrolling treats it
+/// as a ring, of which the visible rows are a window.
+const FRAME_HEIGHT: i32 = 480;
+
+/// Maps screen rows onto frame memory while the panel is hardware
+/// scrolled so that frame memory row `offset` is shown at the top
+struct Scrolled<'a, D> {
+    target: &'a mut D,
+    offset: i32,
+    /// Size of the panel's window onto frame memory
+    size: Size,
+}
+
+impl<D: DrawTarget<Color = Rgb565>> Scrolled<'_, D> {
+    fn frame_row(&self, y: i32) -> i32 {
+        (y + self.offset).rem_euclid(FRAME_HEIGHT)
+    }
+}
+
+impl<D: DrawTarget<Color = Rgb565>> DrawTarget for Scrolled<'_, D> {
+    type Color = Rgb565;
+    type Error = D::Error;
+
+    fn draw_iter<I>(&mut self, pixels: I) -> Result<(), Self::Error>
+    where
+        I: IntoIterator<Item = Pixel<Self::Color>>,
+    {
+        let offset = self.offset;
+        self.target.draw_iter(pixels.into_iter().map(|Pixel(p, color)| {
+            Pixel(Point::new(p.x, (p.y + offset).rem_euclid(FRAME_HEIGHT)), color)
+        }))
+    }
+
+    fn fill_contiguous<I>(&mut self, area: &Rectangle, colors: I) -> Result<(), Self::Error>
+    where
+        I: IntoIterator<Item = Self::Color>,
+    {
+        let y = self.frame_row(area.top_left.y);
+        if y + area.size.height as i32 <= FRAME_HEIGHT {
+            let area = Rectangle::new(Point::new(area.top_left.x, y), area.size);
+            self.target.fill_contiguous(&area, colors)
+        } else {
+            // The area wraps around the end of frame memory
+            let pixels = area.points().zip(colors).map(|(p, color)| Pixel(p, color));
+            self.draw_iter(pixels)
+        }
+    }
+
+    fn fill_solid(&mut self, area: &Rectangle, color: Self::Color) -> Result<(), Self::Error> {
+        let y = self.frame_row(area.top_left.y);
+        let height = area.size.height as i32;
+        let first = height.min(FRAME_HEIGHT - y);
+        let (x, width) = (area.top_left.x, area.size.width);
+        self.target
+            .fill_solid(&Rectangle::new(Point::new(x, y), Size::new(width, first as u32)), color)?;
+        if first < height {
+            let rest = Size::new(width, (height - first) as u32);
+            self.target.fill_solid(&Rectangle::new(Point::new(x, 0), rest), color)?;
+        }
+        Ok(())
+    }
+}
+
+impl<D> Dimensions for Scrolled<'_, D> {
+    fn bounding_box(&self) -> Rectangle {
+        Rectangle::new(Point::zero(), self.size)
+    }
+}
+
+/// Turns what is drawn through it clockwise by `rotation`, onto a
+/// panel that is then as wide as the screen drawn is high
+struct Rotated<'a, D> {
+    target: &'a mut D,
+    rotation: Rotation,
+}
+
+impl<D: DrawTarget<Color = Rgb565>> Rotated<'_, D> {
+    /// The bottom right pixel of the panel
+    fn corner(&self) -> Point {
+        self.target.bounding_box().bottom_right().unwrap_or_default()
+    }
+
+    fn map(rotation: Rotation, corner: Point, p: Point) -> Point {
+        match rotation {
+            Rotation::Deg0 => p,
+            Rotation::Deg90 => Point::new(corner.x - p.y, p.x),
+            Rotation::Deg180 => corner - p,
+            Rotation::Deg270 => Point::new(p.y, corner.y - p.x),
+        }
+    }
+
+    /// Which point on the screen `map` puts at `p` on the panel
+    fn unmap(rotation: Rotation, corner: Point, p: Point) -> Point {
+        match rotation {
+            Rotation::Deg0 => p,
+            Rotation::Deg90 => Point::new(p.y, corner.x - p.x),
+            Rotation::Deg180 => corner - p,
+            Rotation::Deg270 => Point::new(corner.y - p.y, p.x),
+        }
+    }
+
+    fn map_area(&self, area: &Rectangle) -> Rectangle {
+        let Some(bottom_right) = area.bottom_right() else {
+            return Rectangle::zero();
+        };
+        let corner = self.corner();
+        let a = Self::map(self.rotation, corner, area.top_left);
+        let b = Self::map(self.rotation, corner, bottom_right);
+        Rectangle::with_corners(a, b)
+    }
+}
+
+impl<D: DrawTarget<Color = Rgb565>> DrawTarget for Rotated<'_, D> {
+    type Color = Rgb565;
+    type Error = D::Error;
+
+    fn draw_iter<I>(&mut self, pixels: I) -> Result<(), Self::Error>
+    where
+        I: IntoIterator<Item = Pixel<Self::Color>>,
+    {
+        let (rotation, corner) = (self.rotation, self.corner());
+        self.target.draw_iter(
+            pixels
+                .into_iter()
+                .map(|Pixel(p, color)| Pixel(Self::map(rotation, corner, p), color)),
+        )
+    }
+
+    fn fill_contiguous<I>(&mut self, area: &Rectangle, colors: I) -> Result<(), Self::Error>
+    where
+        I: IntoIterator<Item = Self::Color>,
+    {
+        if self.rotation == Rotation::Deg0 {
+            return self.target.fill_contiguous(area, colors);
+        }
+        // The colors come a row of `area` at a time, which the panel
+        // needs in its own order: gather them up and pick them out
+        let colors: Vec<Rgb565> = colors.into_iter().collect();
+        let width = area.size.width as i32;
+        let (rotation, corner) = (self.rotation, self.corner());
+        let mapped = self.map_area(area);
+        self.target.fill_contiguous(
+            &mapped,
+            mapped.points().map(|p| {
+                let p = Self::unmap(rotation, corner, p) - area.top_left;
+                colors
+                    .get((p.y * width + p.x) as usize)
+                    .copied()
+                    .unwrap_or(Rgb565::BLACK)
+            }),
+        )
+    }
+
+    fn fill_solid(&mut self, area: &Rectangle, color: Self::Color) -> Result<(), Self::Error> {
+        let area = self.map_area(area);
+        self.target.fill_solid(&area, color)
+    }
+}
+
+impl<D: Dimensions> Dimensions for Rotated<'_, D> {
+    fn bounding_box(&self) -> Rectangle {
+        let panel = self.target.bounding_box().size;
+        let size = if self.rotation.is_sideways() {
+            Size::new(panel.height, panel.width)
+        } else {
+            panel
+        };
+        Rectangle::new(Point::zero(), size)
+    }
+}
+
+/// Draws an underline `width` pixels long whose lowest row is
+/// `bottom`. Patterned styles are laid out from the left edge of the
+/// screen, so that they run on unbroken across cells.
+fn draw_underline<D: DrawTarget<Color = Rgb565>>(
+    display: &mut D,
+    style: UnderlineStyle,
+    x: i32,
+    bottom: i32,
+    width: u32,
+    color: Rgb565,
+) {
+    let xs = x..x + width as i32;
+    match style {
+        UnderlineStyle::None => {}
+        UnderlineStyle::Single => {
+            display.fill_solid(&Rectangle::new(Point::new(x, bottom), Size::new(width, 1)), color).ok();
+        }
+        UnderlineStyle::Double => {
+            for y in [bottom - 2, bottom] {
+                display.fill_solid(&Rectangle::new(Point::new(x, y), Size::new(width, 1)), color).ok();
+            }
+        }
+        UnderlineStyle::Curly => {
+            const WAVE: [i32; 4] = [0, 1, 2, 1];
+            let pixels = xs.map(|px| Pixel(Point::new(px, bottom - WAVE[px as usize % WAVE.len()]), color));
+            display.draw_iter(pixels).ok();
+        }
+        UnderlineStyle::Dotted => {
+            let pixels = xs.filter(|px| px % 2 == 0).map(|px| Pixel(Point::new(px, bottom), color));
+            display.draw_iter(pixels).ok();
+        }
+        UnderlineStyle::Dashed => {
+            let pixels = xs.filter(|px| px % 6 < 4).map(|px| Pixel(Point::new(px, bottom), color));
+            display.draw_iter(pixels).ok();
+        }
+    }
+}
+
+/// Passes drawing through while keeping the bounding box of it
+struct Damage<'a, D> {
+    target: &'a mut D,
+    area: Option<Rectangle>,
+}
+
+impl<D: Dimensions> Damage<'_, D> {
+    fn add(&mut self, area: &Rectangle) {
+        let area = area.intersection(&self.target.bounding_box());
+        let Some(bottom_right) = area.bottom_right() else {
+            return;
+        };
+        self.area = Some(match self.area {
+            Some(seen) => Rectangle::with_corners(
+                seen.top_left.component_min(area.top_left),
+                seen.bottom_right().unwrap_or(bottom_right).component_max(bottom_right),
+            ),
+            None => area,
+        });
+    }
+}
+
+impl<D: DrawTarget<Color = Rgb565>> DrawTarget for Damage<'_, D> {
+    type Color = Rgb565;
+    type Error = D::Error;
+
+    fn draw_iter<I>(&mut self, pixels: I) -> Result<(), Self::Error>
+    where
+        I: IntoIterator<Item = Pixel<Self::Color>>,
+    {
+        let mut corners: Option<(Point, Point)> = None;
+        let result = self.target.draw_iter(pixels.into_iter().inspect(|Pixel(p, _)| {
+            corners = Some(match corners {
+                Some((min, max)) => (min.component_min(*p), max.component_max(*p)),
+                None => (*p, *p),
+            });
+        }));
+        if let Some((min, max)) = corners {
+            self.add(&Rectangle::with_corners(min, max));
+        }
+        result
+    }
+
+    fn fill_contiguous<I>(&mut self, area: &Rectangle, colors: I) -> Result<(), Self::Error>
+    where
+        I: IntoIterator<Item = Self::Color>,
+    {
+        self.add(area);
+        self.target.fill_contiguous(area, colors)
+    }
+
+    fn fill_solid(&mut self, area: &Rectangle, color: Self::Color) -> Result<(), Self::Error> {
+        self.add(area);
+        self.target.fill_solid(area, color)
+    }
+
+    fn clear(&mut self, color: Self::Color) -> Result<(), Self::Error> {
+        self.add(&self.target.bounding_box());
+        self.target.clear(color)
+    }
+}
+
+impl<D: Dimensions> Dimensions for Damage<'_, D> {
+    fn bounding_box(&self) -> Rectangle {
+        self.target.bounding_box()
+    }
+}
+
+/// Returns the opposite of `color`, as in a photographic negative
+fn inverse(color: Rgb565) -> Rgb565 {
+    Rgb565::new(
+        Rgb565::MAX_R - color.r(),
+        Rgb565::MAX_G - color.g(),
+        Rgb565::MAX_B - color.b(),
+    )
+}
+
+/// Inverts the colors drawn through it while `enabled`
+struct Invert<'a, D> {
+    target: &'a mut D,
+    enabled: bool,
+}
+
+impl<D: DrawTarget<Color = Rgb565>> DrawTarget for Invert<'_, D> {
+    type Color = Rgb565;
+    type Error = D::Error;
+
+    fn draw_iter<I>(&mut self, pixels: I) -> Result<(), Self::Error>
+    where
+        I: IntoIterator<Item = Pixel<Self::Color>>,
+    {
+        let enabled = self.enabled;
+        self.target.draw_iter(
+            pixels
+                .into_iter()
+                .map(|Pixel(p, color)| Pixel(p, if enabled { inverse(color) } else { color })),
+        )
+    }
+
+    fn fill_contiguous<I>(&mut self, area: &Rectangle, colors: I) -> Result<(), Self::Error>
+    where
+        I: IntoIterator<Item = Self::Color>,
+    {
+        let enabled = self.enabled;
+        self.target.fill_contiguous(
+            area,
+            colors.into_iter().map(|color| if enabled { inverse(color) } else { color }),
+        )
+    }
+
+    fn fill_solid(&mut self, area: &Rectangle, color: Self::Color) -> Result<(), Self::Error> {
+        let color = if self.enabled { inverse(color) } else { color };
+        self.target.fill_solid(area, color)
+    }
+
+    fn clear(&mut self, color: Self::Color) -> Result<(), Self::Error> {
+        let color = if self.enabled { inverse(color) } else { color };
+        self.target.clear(color)
+    }
+}
+
+impl<D: Dimensions> Dimensions for Invert<'_, D> {
+    fn bounding_box(&self) -> Rectangle {
+        self.target.bounding_box()
+    }
+}
+
+impl<D: TerminalDisplay> TerminalDisplay for Invert<'_, D> {
+    fn set_scroll_offset(&mut self, offset: u16) {
+        self.target.set_scroll_offset(offset);
+    }
+}
+
+/// Scales what is drawn on a line of double size characters. Points
+/// are given as for a normal line, and are stretched away from the
+/// left edge of the grid and the top of the line. A double height
+/// line shows just its top or bottom half of the stretched glyphs.
+struct LineScale<'a, D> {
+    target: &'a mut D,
+    size: LineSize,
+    left: i32,
+    top: i32,
+    height: i32,
+}
+
+impl<D: DrawTarget<Color = Rgb565>> LineScale<'_, D> {
+    /// The horizontal and vertical scale, and how far up the
+    /// stretched line is shifted
+    fn transform(&self) -> (i32, i32, i32) {
+        match self.size {
+            LineSize::Single => (1, 1, 0),
+            LineSize::DoubleWidth => (2, 1, 0),
+            LineSize::DoubleHeightTop => (2, 2, 0),
+            LineSize::DoubleHeightBottom => (2, 2, self.height),
+        }
+    }
+
+    fn map(&self, p: Point) -> Point {
+        let (sx, sy, shift) = self.transform();
+        Point::new(self.left + (p.x - self.left) * sx, self.top + (p.y - self.top) * sy - shift)
+    }
+
+    fn clip(&self) -> Rectangle {
+        Rectangle::new(
+            Point::new(0, self.top),
+            Size::new(self.target.bounding_box().size.width, self.height as u32),
+        )
+    }
+}
+
+impl<D: DrawTarget<Color = Rgb565>> DrawTarget for LineScale<'_, D> {
+    type Color = Rgb565;
+    type Error = D::Error;
+
+    fn draw_iter<I>(&mut self, pixels: I) -> Result<(), Self::Error>
+    where
+        I: IntoIterator<Item = Pixel<Self::Color>>,
+    {
+        if self.size == LineSize::Single {
+            return self.target.draw_iter(pixels);
+        }
+        let (sx, sy, shift) = self.transform();
+        let (left, top, clip) = (self.left, self.top, self.clip());
+        let scaled = pixels.into_iter().flat_map(move |Pixel(p, color)| {
+            let corner = Point::new(left + (p.x - left) * sx, top + (p.y - top) * sy - shift);
+            (0..sx * sy).map(move |i| Pixel(corner + Point::new(i % sx, i / sx), color))
+        });
+        self.target.draw_iter(scaled.filter(|Pixel(p, _)| clip.contains(*p)))
+    }
+
+    fn fill_contiguous<I>(&mut self, area: &Rectangle, colors: I) -> Result<(), Self::Error>
+    where
+        I: IntoIterator<Item = Self::Color>,
+    {
+        if self.size == LineSize::Single {
+            return self.target.fill_contiguous(area, colors);
+        }
+        self.draw_iter(area.points().zip(colors).map(|(p, color)| Pixel(p, color)))
+    }
+
+    fn fill_solid(&mut self, area: &Rectangle, color: Self::Color) -> Result<(), Self::Error> {
+        if self.size == LineSize::Single {
+            return self.target.fill_solid(area, color);
+        }
+        let (sx, sy, _) = self.transform();
+        let scaled = Rectangle::new(
+            self.map(area.top_left),
+            Size::new(area.size.width * sx as u32, area.size.height * sy as u32),
+        );
+        let visible = scaled.intersection(&self.clip());
+        if visible.is_zero_sized() {
+            return Ok(());
+        }
+        self.target.fill_solid(&visible, color)
+    }
+}
+
+impl<D: Dimensions> Dimensions for LineScale<'_, D> {
+    fn bounding_box(&self) -> Rectangle {
+        self.target.bounding_box()
+    }
+}
+
+/// Rows of glyph height per pixel of slant in synthetic italics
+const ITALIC_SLOPE: i32 = 4;
+
+/// profont has no italic face, so italics are synthesized by
+/// shearing the regular glyphs: pixels above `center_y` are shifted
+/// right and pixels below it are shifted left.
+struct Slanted<'a, D> {
+    target: &'a mut D,
+    center_y: i32,
+}
+
+impl<D: DrawTarget<Color = Rgb565>> DrawTarget for Slanted<'_, D> {
+    type Color = Rgb565;
+    type Error = D::Error;
+
+    fn draw_iter<I>(&mut self, pixels: I) -> Result<(), Self::Error>
+    where
+        I: IntoIterator<Item = Pixel<Self::Color>>,
+    {
+        let center_y = self.center_y;
+        self.target.draw_iter(pixels.into_iter().map(|Pixel(p, color)| {
+            Pixel(Point::new(p.x + (center_y - p.y) / ITALIC_SLOPE, p.y), color)
+        }))
+    }
+}
+
+impl<D: Dimensions> Dimensions for Slanted<'_, D> {
+    fn bounding_box(&self) -> Rectangle {
+        self.target.bounding_box()
+    }
+}
+
+/// Whether `c` is drawn by `draw_box_char` rather than taken from
+/// the font: box drawing and block elements (U+2500 - U+259F),
+/// Braille patterns (U+2800 - U+28FF) and the Powerline separators
+/// (U+E0B0 - U+E0B3)
+fn is_drawn_glyph(c: char) -> bool {
+    matches!(c, '\u{2500}'..='\u{259F}' | '\u{2800}'..='\u{28FF}' | '\u{E0B0}'..='\u{E0B3}')
+}
+
+fn draw_box_char<D: DrawTarget<Color = Rgb565>>(
+    display: &mut D,
     c: char,
     x: i32,
     y: i32,
@@ -656,7 +4116,7 @@ fn draw_box_char(
     let stroke = 1; // Line thickness
 
     // Helper to draw line
-    let line = |display: &mut PicoCalcDisplay, x0, y0, x1, y1| {
+    let line = |display: &mut D, x0, y0, x1, y1| {
         Line::new(Point::new(x0, y0), Point::new(x1, y1))
             .into_styled(PrimitiveStyle::with_stroke(color, stroke))
             .draw(display)
@@ -748,11 +4208,108 @@ fn draw_box_char(
                 color
             ).ok();
         }
+        // Lower one eighth to seven eighths blocks
+        '\u{2581}'..='\u{2587}' => {
+            let eighths = c as u32 - 0x2580;
+            let height = (h * eighths + 4) / 8;
+            display.fill_solid(
+                &Rectangle::new(Point::new(x, y + (h - height) as i32), Size::new(w, height)),
+                color
+            ).ok();
+        }
+        // Left seven eighths to one eighth blocks
+        '\u{2589}'..='\u{258F}' => {
+            let eighths = 0x2590 - c as u32;
+            let width = (w * eighths + 4) / 8;
+            display.fill_solid(
+                &Rectangle::new(Point::new(x, y), Size::new(width, h)),
+                color
+            ).ok();
+        }
+        // Right half block
+        '\u{2590}' => {
+            display.fill_solid(
+                &Rectangle::new(Point::new(x + (w / 2) as i32, y), Size::new(w - w / 2, h)),
+                color
+            ).ok();
+        }
+        // Upper one eighth block
+        '\u{2594}' => {
+            display.fill_solid(
+                &Rectangle::new(Point::new(x, y), Size::new(w, (h + 4) / 8)),
+                color
+            ).ok();
+        }
+        // Right one eighth block
+        '\u{2595}' => {
+            let width = (w + 4) / 8;
+            display.fill_solid(
+                &Rectangle::new(Point::new(x + (w - width) as i32, y), Size::new(width, h)),
+                color
+            ).ok();
+        }
+        // Quadrants, as [upper left, upper right, lower left, lower right]
+        '\u{2596}'..='\u{259F}' => {
+            let quadrants = match c {
+                '\u{2596}' => [false, false, true, false],
+                '\u{2597}' => [false, false, false, true],
+                '\u{2598}' => [true, false, false, false],
+                '\u{2599}' => [true, false, true, true],
+                '\u{259A}' => [true, false, false, true],
+                '\u{259B}' => [true, true, true, false],
+                '\u{259C}' => [true, true, false, true],
+                '\u{259D}' => [false, true, false, false],
+                '\u{259E}' => [false, true, true, false],
+                _ => [false, true, true, true],
+            };
+            let (left, top) = (w / 2, h / 2);
+            for (i, _) in quadrants.into_iter().enumerate().filter(|&(_, filled)| filled) {
+                let (qx, qw) = if i % 2 == 0 { (0, left) } else { (left, w - left) };
+                let (qy, qh) = if i < 2 { (0, top) } else { (top, h - top) };
+                display.fill_solid(
+                    &Rectangle::new(Point::new(x + qx as i32, y + qy as i32), Size::new(qw, qh)),
+                    color
+                ).ok();
+            }
+        }
         // Shades
         '\u{2591}' => draw_shade(display, x, y, w, h, color, 1),
         '\u{2592}' => draw_shade(display, x, y, w, h, color, 2),
         '\u{2593}' => draw_shade(display, x, y, w, h, color, 3),
 
+        // Braille patterns
+        '\u{2800}'..='\u{28FF}' => draw_braille(display, c, x, y, w, h, color),
+
+        // Powerline separators
+        '\u{E0B0}' => { // Solid right-pointing triangle
+            Triangle::new(
+                Point::new(x, y),
+                Point::new(x + w as i32 - 1, cy),
+                Point::new(x, y + h as i32 - 1),
+            )
+            .into_styled(PrimitiveStyle::with_fill(color))
+            .draw(display)
+            .ok();
+        }
+        '\u{E0B1}' => { // Thin right-pointing chevron
+            line(display, x, y, x + w as i32 - 1, cy);
+            line(display, x + w as i32 - 1, cy, x, y + h as i32 - 1);
+        }
+        '\u{E0B2}' => { // Solid left-pointing triangle
+            Triangle::new(
+                Point::new(x + w as i32 - 1, y),
+                Point::new(x, cy),
+                Point::new(x + w as i32 - 1, y + h as i32 - 1),
+            )
+            .into_styled(PrimitiveStyle::with_fill(color))
+            .draw(display)
+            .ok();
+        }
+        '\u{E0B3}' => { // Thin left-pointing chevron
+            line(display, x + w as i32 - 1, y, x, cy);
+            line(display, x, cy, x + w as i32 - 1, y + h as i32 - 1);
+        }
+
         // Rounded corners
         '\u{256D}' => { // Top-left
             Arc::new(Point::new(x + w as i32 / 2, y + h as i32 / 2), w, Angle::from_degrees(180.0), Angle::from_degrees(90.0))
@@ -796,31 +4353,16 @@ fn draw_box_char(
             Line::new(Point::new(cx + 1, y), Point::new(cx + 1, y + h as i32))
                 .into_styled(PrimitiveStyle::with_stroke(color, 1)).draw(display).ok();
         }
-        // Double corners (simplified as single heavy for now to save space/complexity, or proper implementation)
-        '\u{2554}' => { // Double down-right
-            Line::new(Point::new(cx - 1, cy), Point::new(cx - 1, y + h as i32)).into_styled(PrimitiveStyle::with_stroke(color, 1)).draw(display).ok();
-            Line::new(Point::new(cx + 1, cy), Point::new(cx + 1, y + h as i32)).into_styled(PrimitiveStyle::with_stroke(color, 1)).draw(display).ok();
-            Line::new(Point::new(cx, cy - 1), Point::new(x + w as i32, cy - 1)).into_styled(PrimitiveStyle::with_stroke(color, 1)).draw(display).ok();
-            Line::new(Point::new(cx, cy + 1), Point::new(x + w as i32, cy + 1)).into_styled(PrimitiveStyle::with_stroke(color, 1)).draw(display).ok();
-        }
-        '\u{2557}' => { // Double down-left
-            Line::new(Point::new(cx - 1, cy), Point::new(cx - 1, y + h as i32)).into_styled(PrimitiveStyle::with_stroke(color, 1)).draw(display).ok();
-            Line::new(Point::new(cx + 1, cy), Point::new(cx + 1, y + h as i32)).into_styled(PrimitiveStyle::with_stroke(color, 1)).draw(display).ok();
-            Line::new(Point::new(x, cy - 1), Point::new(cx, cy - 1)).into_styled(PrimitiveStyle::with_stroke(color, 1)).draw(display).ok();
-            Line::new(Point::new(x, cy + 1), Point::new(cx, cy + 1)).into_styled(PrimitiveStyle::with_stroke(color, 1)).draw(display).ok();
-        }
-        '\u{255A}' => { // Double up-right
-            Line::new(Point::new(cx - 1, y), Point::new(cx - 1, cy)).into_styled(PrimitiveStyle::with_stroke(color, 1)).draw(display).ok();
-            Line::new(Point::new(cx + 1, y), Point::new(cx + 1, cy)).into_styled(PrimitiveStyle::with_stroke(color, 1)).draw(display).ok();
-            Line::new(Point::new(cx, cy - 1), Point::new(x + w as i32, cy - 1)).into_styled(PrimitiveStyle::with_stroke(color, 1)).draw(display).ok();
-            Line::new(Point::new(cx, cy + 1), Point::new(x + w as i32, cy + 1)).into_styled(PrimitiveStyle::with_stroke(color, 1)).draw(display).ok();
-        }
-        '\u{255D}' => { // Double up-left
-            Line::new(Point::new(cx - 1, y), Point::new(cx - 1, cy)).into_styled(PrimitiveStyle::with_stroke(color, 1)).draw(display).ok();
-            Line::new(Point::new(cx + 1, y), Point::new(cx + 1, cy)).into_styled(PrimitiveStyle::with_stroke(color, 1)).draw(display).ok();
-            Line::new(Point::new(x, cy - 1), Point::new(cx, cy - 1)).into_styled(PrimitiveStyle::with_stroke(color, 1)).draw(display).ok();
-            Line::new(Point::new(x, cy + 1), Point::new(cx, cy + 1)).into_styled(PrimitiveStyle::with_stroke(color, 1)).draw(display).ok();
-        }
+        // Double corners and junctions, by which arms are present
+        '\u{2554}' => draw_double(display, x, y, w, h, color, [false, true, false, true]),
+        '\u{2557}' => draw_double(display, x, y, w, h, color, [false, true, true, false]),
+        '\u{255A}' => draw_double(display, x, y, w, h, color, [true, false, false, true]),
+        '\u{255D}' => draw_double(display, x, y, w, h, color, [true, false, true, false]),
+        '\u{2560}' => draw_double(display, x, y, w, h, color, [true, true, false, true]),
+        '\u{2563}' => draw_double(display, x, y, w, h, color, [true, true, true, false]),
+        '\u{2566}' => draw_double(display, x, y, w, h, color, [false, true, true, true]),
+        '\u{2569}' => draw_double(display, x, y, w, h, color, [true, false, true, true]),
+        '\u{256C}' => draw_double(display, x, y, w, h, color, [true, true, true, true]),
 
         _ => {
             // Fallback for unhandled box chars: draw a small rectangle
@@ -832,7 +4374,96 @@ fn draw_box_char(
     }
 }
 
-fn draw_shade(display: &mut PicoCalcDisplay, x: i32, y: i32, w: u32, h: u32, color: Rgb565, density: u8) {
+/// Draw double lines from the center of the cell along the arms
+/// given as `[up, down, left, right]`. Each arm is a pair of rails
+/// either side of the center line; where arms meet, the outer rails
+/// join in a corner and the inner rails stop at each other, so the
+/// border reads as continuous.
+fn draw_double<D: DrawTarget<Color = Rgb565>>(
+    display: &mut D,
+    x: i32,
+    y: i32,
+    w: u32,
+    h: u32,
+    color: Rgb565,
+    [up, down, left, right]: [bool; 4],
+) {
+    let cx = x + (w / 2) as i32;
+    let cy = y + (h / 2) as i32;
+    let (x_end, y_end) = (x + w as i32, y + h as i32);
+    let mut line = |x0, y0, x1, y1| {
+        Line::new(Point::new(x0, y0), Point::new(x1, y1))
+            .into_styled(PrimitiveStyle::with_stroke(color, 1))
+            .draw(display)
+            .ok();
+    };
+
+    // Horizontal rails above and below the center, each interrupted
+    // by the vertical arm on its side
+    for (rail_y, cross) in [(cy - 1, up), (cy + 1, down)] {
+        if left {
+            let end = if cross { cx - 1 } else if right { x_end } else { cx + 1 };
+            line(x, rail_y, end, rail_y);
+        }
+        if right {
+            let start = if cross { cx + 1 } else if left { continue } else { cx - 1 };
+            line(start, rail_y, x_end, rail_y);
+        }
+    }
+    // Vertical rails left and right of the center, each interrupted
+    // by the horizontal arm on its side
+    for (rail_x, cross) in [(cx - 1, left), (cx + 1, right)] {
+        if up {
+            let end = if cross { cy - 1 } else if down { y_end } else { cy + 1 };
+            line(rail_x, y, rail_x, end);
+        }
+        if down {
+            let start = if cross { cy + 1 } else if up { continue } else { cy - 1 };
+            line(rail_x, start, rail_x, y_end);
+        }
+    }
+}
+
+/// Braille patterns number their dots down the left column and then
+/// the right, with the bottom row's dots added last; the low 8 bits
+/// of the code point say which are raised. This maps each bit to its
+/// (column, row) in the 2x4 grid.
+const BRAILLE_DOTS: [(u32, u32); 8] = [
+    (0, 0),
+    (0, 1),
+    (0, 2),
+    (1, 0),
+    (1, 1),
+    (1, 2),
+    (0, 3),
+    (1, 3),
+];
+
+fn draw_braille<D: DrawTarget<Color = Rgb565>>(
+    display: &mut D,
+    c: char,
+    x: i32,
+    y: i32,
+    w: u32,
+    h: u32,
+    color: Rgb565,
+) {
+    let bits = c as u32 & 0xff;
+    let (cell_w, cell_h) = (w / 2, h / 4);
+    let dot = (cell_w / 2).max(1);
+    for (bit, &(col, row)) in BRAILLE_DOTS.iter().enumerate() {
+        if bits & (1 << bit) == 0 {
+            continue;
+        }
+        let dot_x = x + (col * cell_w + (cell_w - dot) / 2) as i32;
+        let dot_y = y + (row * cell_h + cell_h.saturating_sub(dot) / 2) as i32;
+        display
+            .fill_solid(&Rectangle::new(Point::new(dot_x, dot_y), Size::new(dot, dot)), color)
+            .ok();
+    }
+}
+
+fn draw_shade<D: DrawTarget<Color = Rgb565>>(display: &mut D, x: i32, y: i32, w: u32, h: u32, color: Rgb565, density: u8) {
     for py in 0..h {
         for px in 0..w {
             let on = match density {
@@ -847,3 +4478,6 @@ fn draw_shade(display: &mut PicoCalcDisplay, x: i32, y: i32, w: u32, h: u32, col
         }
     }
 }
+
+#[cfg(test)]
+mod tests;
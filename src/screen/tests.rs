@@ -0,0 +1,759 @@
+//! Tests of the terminal emulation, run on the host with `make test`.
+//! Rendering is checked against embedded-graphics' `MockDisplay`, and
+//! against `Panel` where the hardware scroll comes into it.
+
+use super::*;
+use embedded_graphics::mock_display::MockDisplay;
+
+impl TerminalDisplay for MockDisplay<Rgb565> {
+    fn set_scroll_offset(&mut self, offset: u16) {
+        // It has no frame memory beyond what is shown
+        assert_eq!(offset, 0, "MockDisplay can't be hardware scrolled");
+    }
+}
+
+/// Color of frame memory that nothing has been drawn to
+const UNDRAWN: Rgb565 = Rgb565::new(1, 2, 3);
+
+/// Frame memory of a panel `width` pixels across which, like the
+/// ILI9488, shows the rows from its scroll offset on at the top
+struct Panel {
+    width: usize,
+    frame: Vec<Rgb565>,
+    offset: usize,
+}
+
+impl Panel {
+    fn new() -> Self {
+        Self::with_width(SCREEN_WIDTH as usize)
+    }
+
+    fn with_width(width: usize) -> Self {
+        Self {
+            width,
+            frame: vec![UNDRAWN; width * FRAME_HEIGHT as usize],
+            offset: 0,
+        }
+    }
+
+    /// The color on the glass at `x`, `y`
+    fn pixel(&self, x: usize, y: usize) -> Rgb565 {
+        let row = (y + self.offset) % FRAME_HEIGHT as usize;
+        self.frame[row * self.width + x]
+    }
+
+    /// A panel whose scroll offset matches this one, for drawing
+    /// the same model into from scratch
+    fn fresh(&self) -> Self {
+        Self {
+            offset: self.offset,
+            ..Self::with_width(self.width)
+        }
+    }
+}
+
+impl OriginDimensions for Panel {
+    fn size(&self) -> Size {
+        Size::new(self.width as u32, FRAME_HEIGHT as u32)
+    }
+}
+
+impl DrawTarget for Panel {
+    type Color = Rgb565;
+    type Error = core::convert::Infallible;
+
+    fn draw_iter<I>(&mut self, pixels: I) -> Result<(), Self::Error>
+    where
+        I: IntoIterator<Item = Pixel<Self::Color>>,
+    {
+        for Pixel(p, color) in pixels {
+            if (0..self.width as i32).contains(&p.x) && (0..FRAME_HEIGHT).contains(&p.y) {
+                self.frame[p.y as usize * self.width + p.x as usize] = color;
+            }
+        }
+        Ok(())
+    }
+}
+
+impl TerminalDisplay for Panel {
+    fn set_scroll_offset(&mut self, offset: u16) {
+        self.offset = offset as usize;
+    }
+}
+
+/// Asserts that what is on the glass of the two panels is the same
+/// over the model's screen
+fn assert_same_glass(model: &ScreenModel, a: &Panel, b: &Panel) {
+    let (width, height) = model.screen_size();
+    for y in 0..height as usize {
+        for x in 0..width as usize {
+            assert_eq!(a.pixel(x, y), b.pixel(x, y), "at {x},{y}");
+        }
+    }
+}
+
+/// A screen laid out on 64 pixels square, as big as `MockDisplay` is
+fn small_screen() -> Screen {
+    let mut screen = Screen::new();
+    screen.set_panel_size(64, 64);
+    screen
+}
+
+fn render(model: &mut ScreenModel) -> MockDisplay<Rgb565> {
+    let mut display = MockDisplay::new();
+    // Backgrounds are drawn before the glyphs on them
+    display.set_allow_overdraw(true);
+    display.set_allow_out_of_bounds_drawing(true);
+    model.update_display(&mut display);
+    display
+}
+
+/// The color at pixel `px`, `py` within the cell at column `x` of
+/// row `y`
+fn cell_pixel(display: &MockDisplay<Rgb565>, model: &ScreenModel, x: usize, y: usize, px: u32, py: u32) -> Option<Rgb565> {
+    let width = model.font.character_size.width + model.font.character_spacing;
+    let point = Point::new(
+        (x as u32 * width + px) as i32,
+        (y as u32 * model.cell_height() + py) as i32,
+    );
+    display.get_pixel(point)
+}
+
+#[test]
+fn renders_cells_in_palette_colors() {
+    let mut screen = small_screen();
+    screen.print("\x1b[?25l\x1b[7m \x1b[m \x1b[41m \x1b[m");
+    let display = render(&mut screen);
+    let palette = Palette::DEFAULT;
+    assert_eq!(cell_pixel(&display, &screen, 0, 0, 1, 1), Some(palette.fg));
+    assert_eq!(cell_pixel(&display, &screen, 1, 0, 1, 1), Some(palette.bg));
+    assert_eq!(cell_pixel(&display, &screen, 2, 0, 1, 1), Some(palette.ansi[1]));
+    // The rest of the row is cleared too
+    assert_eq!(cell_pixel(&display, &screen, 3, 1, 1, 1), Some(palette.bg));
+}
+
+#[test]
+fn renders_box_drawing_across_the_cell() {
+    let mut screen = small_screen();
+    screen.print("\x1b[?25l\u{2500}\u{2502}");
+    let display = render(&mut screen);
+    let fg = Palette::DEFAULT.fg;
+    let width = screen.font.character_size.width;
+    let height = screen.font.character_size.height;
+    // A horizontal line reaches both edges of its cell
+    for px in 0..width {
+        assert_eq!(cell_pixel(&display, &screen, 0, 0, px, height / 2), Some(fg), "at {px}");
+    }
+    assert_ne!(cell_pixel(&display, &screen, 0, 0, width / 2, 0), Some(fg));
+    // and a vertical one the top and bottom of its
+    for py in 0..height {
+        assert_eq!(cell_pixel(&display, &screen, 1, 0, width / 2, py), Some(fg), "at {py}");
+    }
+}
+
+#[test]
+fn renders_underline_on_the_bottom_row() {
+    let mut screen = small_screen();
+    screen.print("\x1b[?25l\x1b[4m \x1b[24m ");
+    let display = render(&mut screen);
+    let fg = Palette::DEFAULT.fg;
+    let bottom = screen.font.character_size.height - 1;
+    assert_eq!(cell_pixel(&display, &screen, 0, 0, 0, bottom), Some(fg));
+    assert_eq!(cell_pixel(&display, &screen, 0, 0, 0, 0), Some(Palette::DEFAULT.bg));
+    assert_eq!(cell_pixel(&display, &screen, 1, 0, 0, bottom), Some(Palette::DEFAULT.bg));
+}
+
+#[test]
+fn renders_the_cursor_shape() {
+    let mut screen = small_screen();
+    screen.print("\x1b[2;3H");
+    let display = render(&mut screen);
+    let cursor = Palette::DEFAULT.cursor;
+    let width = screen.font.character_size.width;
+    assert_eq!(cell_pixel(&display, &screen, 2, 1, 0, 0), Some(cursor));
+    assert_eq!(cell_pixel(&display, &screen, 2, 1, width - 1, 0), Some(cursor));
+    assert_eq!(cell_pixel(&display, &screen, 1, 1, 0, 0), Some(Palette::DEFAULT.bg));
+
+    // A bar covers just the left of the cell
+    screen.print("\x1b[6 q");
+    let display = render(&mut screen);
+    assert_eq!(cell_pixel(&display, &screen, 2, 1, 0, 0), Some(cursor));
+    assert_eq!(cell_pixel(&display, &screen, 2, 1, width - 1, 0), Some(Palette::DEFAULT.bg));
+
+    // and a hidden cursor not at all
+    screen.print("\x1b[?25l");
+    let display = render(&mut screen);
+    assert_eq!(cell_pixel(&display, &screen, 2, 1, 0, 0), Some(Palette::DEFAULT.bg));
+}
+
+#[test]
+fn draws_nothing_when_nothing_changed() {
+    let mut screen = small_screen();
+    screen.print("\x1b[?25lhello");
+    render(&mut screen);
+    let display = render(&mut screen);
+    assert_eq!(display, MockDisplay::new());
+}
+
+#[test]
+fn hardware_scroll_matches_a_full_repaint() {
+    let mut screen = Screen::new();
+    let mut panel = Panel::new();
+    for i in 0..120 {
+        screen.print(&format!("line {i} \x1b[3{}mcolored\x1b[m\r\n", i % 8));
+        if i % 3 != 0 {
+            screen.update_display(&mut panel);
+        }
+    }
+    assert_ne!(panel.offset, 0);
+    let mut fresh = panel.fresh();
+    screen.full_repaint = true;
+    screen.update_display(&mut fresh);
+    assert_same_glass(&screen, &panel, &fresh);
+}
+
+/// A screen `cols` by `rows` cells, whatever the font
+fn grid(cols: usize, rows: usize) -> Screen {
+    let mut screen = Screen::new();
+    screen.resize(cols, rows);
+    screen
+}
+
+fn cursor(model: &ScreenModel) -> (usize, usize) {
+    (model.cursor_x, model.cursor_y)
+}
+
+#[test]
+fn parses_cursor_moves() {
+    let mut screen = grid(10, 4);
+    screen.parse_bytes(b"\x1b[2;3Ha\x1b[4Gb\x1b[Ac\x1b[2Dd\x1b[Be\x1b[Hf");
+    assert_eq!(screen.to_text(), "f  dc\n  abe\n\n");
+    assert_eq!(cursor(&screen), (1, 0));
+    // Moves stop at the edges
+    screen.parse_bytes(b"\x1b[99;99H");
+    assert_eq!(cursor(&screen), (9, 3));
+    screen.parse_bytes(b"\x1b[99A\x1b[99D");
+    assert_eq!(cursor(&screen), (0, 0));
+}
+
+#[test]
+fn parses_erases() {
+    let mut screen = grid(6, 3);
+    screen.parse_bytes(b"abcdef\r\nghijkl\r\nmnopqr");
+    screen.parse_bytes(b"\x1b[2;3H\x1b[K");
+    assert_eq!(screen.to_text(), "abcdef\ngh\nmnopqr");
+    screen.parse_bytes(b"\x1b[1K");
+    assert_eq!(screen.to_text(), "abcdef\n\nmnopqr");
+    screen.parse_bytes(b"\x1b[1;4H\x1b[2K\x1b[3;2H\x1b[J");
+    assert_eq!(screen.to_text(), "\n\nm");
+    // Erasing part of the screen doesn't move the cursor
+    assert_eq!(cursor(&screen), (1, 2));
+    screen.parse_bytes(b"\x1b[2J");
+    assert_eq!(screen.to_text(), "\n\n");
+}
+
+#[test]
+fn parses_sgr() {
+    let mut screen = grid(10, 2);
+    screen.parse_bytes(b"\x1b[1;31ma\x1b[22;44mb\x1b[38;5;200;4mc\x1b[38;2;1;2;3;7md\x1b[me");
+    let attrs = |x| screen.cell(x, 0).unwrap().1;
+    assert!(attrs(0).bold());
+    assert_eq!(attrs(0).fg(), Color::Indexed(1));
+    assert!(!attrs(1).bold());
+    assert_eq!((attrs(1).fg(), attrs(1).bg()), (Color::Indexed(1), Color::Indexed(4)));
+    assert_eq!(attrs(2).fg(), Color::Indexed(200));
+    assert!(attrs(2).underline());
+    assert_eq!(attrs(3).fg(), Color::Rgb(1, 2, 3));
+    assert!(attrs(3).reverse() && attrs(3).underline());
+    assert_eq!(attrs(4), Attrs::default());
+    assert_eq!(screen.to_text(), "abcde\n");
+}
+
+#[test]
+fn wraps_at_the_last_column() {
+    let mut screen = grid(4, 3);
+    screen.parse_bytes(b"abcd");
+    // The cursor waits on the last column until the next character
+    assert_eq!(cursor(&screen), (3, 0));
+    assert_eq!(screen.to_text(), "abcd\n\n");
+    screen.parse_bytes(b"ef");
+    assert_eq!(screen.to_text(), "abcd\nef\n");
+    assert_eq!(cursor(&screen), (2, 1));
+    // A carriage return cancels the pending wrap
+    screen.parse_bytes(b"gh\rX");
+    assert_eq!(screen.to_text(), "abcd\nXfgh\n");
+    // and without autowrap the last column is overwritten
+    screen.parse_bytes(b"\x1b[?7l\x1b[3;1Hwxyz12");
+    assert_eq!(screen.to_text(), "abcd\nXfgh\nwxy2");
+    assert_eq!(cursor(&screen), (3, 2));
+}
+
+#[test]
+fn scrolls_past_the_last_row() {
+    let mut screen = grid(5, 3);
+    screen.parse_bytes(b"one\r\ntwo\r\nthree\r\nfour\r\nfive");
+    assert_eq!(screen.to_text(), "three\nfour\nfive");
+    assert_eq!(cursor(&screen), (4, 2));
+    assert_eq!(screen.scrollback.len(), 2);
+    // Wrapping off the bottom scrolls too
+    screen.parse_bytes(b"sixty");
+    assert_eq!(screen.to_text(), "four\nfives\nixty");
+    assert_eq!(cursor(&screen), (4, 2));
+    screen.scroll_view_up(2);
+    assert_eq!(screen.to_text(), "two\nthree\nfour");
+    assert_eq!(screen.scrollback.len(), 3);
+}
+
+#[test]
+fn renders_scrollback_wider_than_the_grid() {
+    let mut screen = grid(10, 4);
+    screen.print("\x1b[?25l");
+    for i in 0..8 {
+        screen.print(&format!("\x1b[7m{i:-<10}\x1b[m\r\n"));
+    }
+    screen.resize(5, 4);
+    screen.scroll_view_up(4);
+    let mut panel = Panel::new();
+    screen.update_display(&mut panel);
+    // The lines are in reverse video, so each cell drawn is filled
+    // with the text color. Only the grid's 5 columns are.
+    let width = screen.font.character_size.width + screen.font.character_spacing;
+    let height = screen.font.character_size.height;
+    for y in 0..4 {
+        for x in 0..10 {
+            let pixel = panel.pixel((x * width) as usize, (y * height) as usize);
+            if x < 5 {
+                assert_eq!(pixel, Palette::DEFAULT.fg, "at {x},{y}");
+            } else {
+                assert_ne!(pixel, Palette::DEFAULT.fg, "at {x},{y}");
+            }
+        }
+    }
+}
+
+mod fuzz {
+    use super::*;
+    use proptest::prelude::*;
+
+    /// Pieces of escape sequences, for random input to get further
+    /// into the parser than random bytes alone would
+    const FRAGMENTS: &[&str] = &[
+        "\x1b[", "\x1b]", "\x1b#", "\x1b(", "\x1b7", "\x1b8", "\x1bD", "\x1bM", "\x1bE", "\x1bc",
+        "\x07", "\x1b\\", ";", "?", "0", "1", "9", "65535", "A", "B", "C", "D", "G", "H", "J",
+        "K", "L", "M", "P", "S", "T", "X", "@", "b", "d", "h", "l", "m", "r", "s", "u", " q",
+        "\"q", "!p", "?1049h", "?1049l", "?6h", "?7l", "3", "6", "\r\n", "\t", "\x08", "é", "字",
+    ];
+
+    #[derive(Debug, Clone)]
+    enum Step {
+        Bytes(Vec<u8>),
+        Resize(usize, usize),
+        Font(bool),
+        ScrollView(usize),
+        Render,
+    }
+
+    fn step() -> impl Strategy<Value = Step> {
+        let fragments = prop::collection::vec(prop::sample::select(FRAGMENTS), 1..16)
+            .prop_map(|fragments| Step::Bytes(fragments.concat().into_bytes()));
+        prop_oneof![
+            8 => fragments,
+            4 => prop::collection::vec(any::<u8>(), 1..64).prop_map(Step::Bytes),
+            // Enough new lines to fill some scrollback
+            1 => (1..60usize).prop_map(|n| Step::Bytes(b"x\r\n".repeat(n))),
+            1 => (0..60usize, 0..12usize).prop_map(|(cols, rows)| Step::Resize(cols, rows)),
+            1 => any::<bool>().prop_map(Step::Font),
+            1 => (0..40usize).prop_map(Step::ScrollView),
+            1 => Just(Step::Render),
+        ]
+    }
+
+    proptest! {
+        #[test]
+        fn survives_any_input(steps in prop::collection::vec(step(), 1..40)) {
+            let mut screen = Screen::new();
+            let mut panel = Panel::new();
+            for step in steps {
+                match step {
+                    Step::Bytes(bytes) => screen.parse_bytes(&bytes),
+                    Step::Resize(cols, rows) => screen.resize(cols, rows),
+                    Step::Font(true) => screen.increase_font(),
+                    Step::Font(false) => screen.decrease_font(),
+                    Step::ScrollView(n) => screen.scroll_view_up(n),
+                    Step::Render => {
+                        screen.update_display(&mut panel);
+                    }
+                }
+                prop_assert!(screen.cursor_x < screen.cols);
+                prop_assert!(screen.cursor_y < screen.rows);
+                prop_assert!(screen.scroll_top <= screen.scroll_bottom);
+                prop_assert!(screen.scroll_bottom < screen.rows);
+            }
+            screen.update_display(&mut panel);
+        }
+    }
+}
+
+#[test]
+fn reset_draws_from_the_scrolled_offset() {
+    let mut screen = Screen::new();
+    let mut panel = Panel::new();
+    for i in 0..60 {
+        screen.print(&format!("line {i}\r\n"));
+        screen.update_display(&mut panel);
+    }
+    assert_ne!(panel.offset, 0);
+    screen.print("\x1bcafter the reset");
+    screen.update_display(&mut panel);
+
+    let mut fresh = Screen::new();
+    fresh.print("after the reset");
+    let mut fresh_panel = Panel::new();
+    fresh.update_display(&mut fresh_panel);
+    assert_same_glass(&screen, &panel, &fresh_panel);
+}
+
+#[test]
+fn scrolls_by_repainting_without_hardware_scroll() {
+    let mut screen = Screen::new();
+    screen.set_hardware_scroll(false);
+    let mut panel = Panel::new();
+    for i in 0..60 {
+        screen.print(&format!("line {i}\r\n"));
+        screen.update_display(&mut panel);
+    }
+    assert_eq!(panel.offset, 0);
+    let mut fresh = panel.fresh();
+    screen.full_repaint = true;
+    screen.update_display(&mut fresh);
+    assert_same_glass(&screen, &panel, &fresh);
+}
+
+#[test]
+fn reset_returns_to_the_user_palette() {
+    let mut screen = Screen::new();
+    let mut palette = Palette::DEFAULT;
+    palette.fg = Rgb565::GREEN;
+    screen.set_palette(palette);
+    screen.print("\x1b]4;1;#102030\x07\x1b]10;#405060\x07");
+    assert_eq!(screen.palette.ansi[1], rgb(0x10, 0x20, 0x30));
+    assert_eq!(screen.palette.fg, rgb(0x40, 0x50, 0x60));
+    screen.print("\x1bc");
+    assert_eq!(screen.palette, palette);
+}
+
+#[test]
+fn reset_keeps_the_color_correction() {
+    let mut screen = Screen::new();
+    let correction = ColorCorrection::new(0.5, 0.9);
+    screen.set_color_correction(correction);
+    screen.print("\x1bc");
+    assert_eq!(screen.color_correction, correction);
+    let color = Color::Rgb(0x40, 0x40, 0x40);
+    assert_eq!(
+        color.to_rgb565(&screen.palette, &screen.color_correction),
+        correction.apply(0x40, 0x40, 0x40),
+    );
+    assert_ne!(correction.apply(0x40, 0x40, 0x40), ColorCorrection::IDENTITY.apply(0x40, 0x40, 0x40));
+}
+
+#[test]
+fn scrolls_a_single_row_grid() {
+    let mut screen = grid(1, 1);
+    screen.parse_bytes(b"a\r\nb");
+    assert_eq!(screen.to_text(), "b");
+    assert_eq!(screen.scrollback.len(), 1);
+    assert_eq!(screen.scrollback[0].chars, ['a']);
+    // Wrapping scrolls too
+    screen.parse_bytes(b"c");
+    assert_eq!(screen.to_text(), "c");
+    assert_eq!(screen.scrollback.len(), 2);
+    assert_eq!(cursor(&screen), (0, 0));
+    // and reverse index clears the row
+    screen.parse_bytes(b"\x1bM");
+    assert_eq!(screen.to_text(), "");
+}
+
+#[test]
+fn column_mode_switches_font_and_clears() {
+    let mut screen = Screen::new();
+    let (font, cols) = (screen.font, screen.cols);
+    screen.print("before\x1b[3;5H\x1b[?3h");
+    // No font fits 132 columns on the panel, so the smallest is used
+    assert!(core::ptr::eq(screen.font, FONTS[0]));
+    assert!(screen.cols > cols && screen.cols < WIDE_COLUMNS);
+    assert_eq!(screen.width() as usize, screen.cols);
+    assert_eq!(screen.to_text().trim(), "");
+    assert_eq!(cursor(&screen), (0, 0));
+
+    screen.print("wide\x1b[3;5H\x1b[?3l");
+    assert!(core::ptr::eq(screen.font, font));
+    assert_eq!(screen.cols, cols);
+    assert_eq!(screen.to_text().trim(), "");
+    assert_eq!(cursor(&screen), (0, 0));
+}
+
+#[test]
+fn column_mode_uses_the_largest_font_reaching_132_columns() {
+    let mut screen = Screen::new();
+    screen.set_panel_size(MAX_PANEL_WIDTH, 320);
+    screen.print("\x1b[?3h");
+    assert!(screen.cols >= WIDE_COLUMNS);
+    let index = FONTS.iter().position(|&font| core::ptr::eq(font, screen.font)).unwrap();
+    let larger = FONTS[index + 1];
+    assert!(grid_size(larger, screen.line_spacing, screen.screen_size()).0 < WIDE_COLUMNS);
+}
+
+#[test]
+fn yanks_a_block_selection() {
+    let mut screen = grid(6, 4);
+    screen.print("abcdef\r\nghijkl\r\nmnopqr\x1b[1;2H");
+    screen.enter_copy_mode();
+    screen.copy_mode_toggle_block();
+    for motion in [CopyMotion::Right, CopyMotion::Right, CopyMotion::Down, CopyMotion::Down] {
+        screen.copy_mode_move(motion);
+    }
+    screen.exit_copy_mode(true);
+    assert_eq!(screen.clipboard(), "bcd\nhij\nnop");
+}
+
+#[test]
+fn packed_colors_round_trip() {
+    let mut colors = NAMED_COLORS.to_vec();
+    colors.extend([Color::DefaultFg, Color::DefaultBg]);
+    colors.extend((0..=255).map(Color::Indexed));
+    for v in [0, 1, 0x7f, 0x80, 0xfe, 0xff] {
+        colors.extend([Color::Rgb(v, 0, 0), Color::Rgb(0, v, 0), Color::Rgb(0, 0, v), Color::Rgb(v, v, v)]);
+    }
+    colors.push(Color::Rgb(0x12, 0x34, 0x56));
+    for color in colors {
+        assert_eq!(PackedColor::new(color).get(), color);
+    }
+}
+
+#[test]
+fn scrollback_ring_lines_up_after_many_lines() {
+    let mut screen = grid(8, 4);
+    screen.set_max_scrollback(50);
+    for i in 0..10_000 {
+        screen.print(&format!("\r\n{i}"));
+    }
+    assert_eq!(screen.scrollback.len(), 50);
+    assert_eq!(screen.total_lines(), 54);
+    // Line numbers run on from the oldest kept in scrollback through
+    // to the bottom of the screen
+    for line in 0..screen.total_lines() {
+        let text: String = screen.abs_line(line).chars.iter().collect();
+        assert_eq!(text.trim_end(), (9946 + line).to_string());
+    }
+    assert_eq!(screen.to_text(), "9996\n9997\n9998\n9999");
+    screen.scroll_view_up(3);
+    assert_eq!(screen.to_text(), "9993\n9994\n9995\n9996");
+    screen.scroll_view_to_top();
+    assert_eq!(screen.iter_lines().next().unwrap()[..4], ['9', '9', '4', '6']);
+}
+
+#[test]
+fn restoring_the_cursor_restores_the_pen() {
+    let mut screen = grid(10, 4);
+    for (save, restore) in [("\x1b7", "\x1b8"), ("\x1b[s", "\x1b[u"), ("\x1b[?1049h", "\x1b[?1049l")] {
+        screen.print("\x1b[m\x1b[2;3H\x1b[1;32;44m");
+        let pen = screen.current_attrs;
+        screen.print(save);
+        screen.print("\x1b[m\x1b[4H\x1b[35m");
+        assert_ne!(screen.current_attrs, pen);
+        screen.print(restore);
+        assert_eq!(screen.current_attrs, pen, "after {restore:?}");
+        assert!(pen.bold());
+        assert_eq!((pen.fg(), pen.bg()), (Color::Indexed(2), Color::Indexed(4)));
+        assert_eq!(cursor(&screen), (2, 1));
+    }
+}
+
+#[test]
+fn shadow_framebuffer_cell_size() {
+    // As given in the cost of SHADOW_FRAMEBUFFER
+    assert_eq!(size_of::<Option<DrawnCell>>(), 20);
+}
+
+#[test]
+fn draws_every_cell_across_the_widest_panel() {
+    let mut screen = Screen::new();
+    screen.set_panel_size(MAX_PANEL_WIDTH, 320);
+    screen.set_font(FONTS[0]);
+    assert_eq!(screen.cols, MAX_DRAWN_CELLS);
+    screen.print(&format!("\x1b[?25l\x1b[7m{:1$}", "", screen.cols));
+    let mut panel = Panel::with_width(MAX_PANEL_WIDTH as usize);
+    screen.update_display(&mut panel);
+    let cell_width = screen.font.character_size.width + screen.font.character_spacing;
+    let last = (screen.cols as u32 - 1) * cell_width;
+    assert_eq!(panel.pixel(last as usize, 0), Palette::DEFAULT.fg);
+}
+
+#[test]
+fn reusing_a_hyperlink_entry_unlinks_its_cells() {
+    let mut screen = grid(20, 4);
+    for i in 0..=MAX_HYPERLINKS {
+        screen.print(&format!("\x1b]8;;https://{i}.example\x1b\\{i}\x1b]8;;\x1b\\"));
+    }
+    // The first entry went to the last link, so the first cell
+    // lost its link rather than taking on the new one
+    assert_eq!(screen.link_at(0, 0), None);
+    assert_eq!(screen.link_at(1, 0), Some("https://1.example"));
+    let last = format!("https://{MAX_HYPERLINKS}.example");
+    assert_eq!(screen.link_at(MAX_HYPERLINKS, 0), Some(last.as_str()));
+}
+
+/// The URL spans found on viewport row y
+fn links(screen: &ScreenModel, y: usize) -> Vec<(usize, usize)> {
+    screen.links_on_line(y).collect()
+}
+
+#[test]
+fn finds_http_and_https_urls() {
+    let mut screen = grid(30, 4);
+    screen.print("http://a.io or https://b.io\r\n");
+    assert_eq!(links(&screen, 0), [(0, 11), (15, 27)]);
+}
+
+#[test]
+fn finds_urls_up_to_the_end_of_the_line() {
+    let mut screen = grid(20, 4);
+    screen.print("go https://ex.com/ab\r\n");
+    assert_eq!(links(&screen, 0), [(3, 20)]);
+    // A URL cut by wrapping is found up to the edge, and its
+    // continuation, lacking a scheme, isn't a URL of its own
+    screen.print("go https://example.com/abc\r\n");
+    assert_eq!(links(&screen, 1), [(3, 20)]);
+    assert_eq!(links(&screen, 2), []);
+}
+
+#[test]
+fn leaves_trailing_punctuation_out_of_urls() {
+    let mut screen = grid(40, 4);
+    screen.print("(see https://a.io/x). https://b.io/y?, \r\n");
+    assert_eq!(links(&screen, 0), [(5, 19), (22, 36)]);
+}
+
+#[test]
+fn keeps_the_first_urls_of_a_crowded_line() {
+    let mut screen = grid(50, 4);
+    screen.print("http://a http://b http://c http://d http://e\r\n");
+    assert_eq!(links(&screen, 0).len(), MAX_LINE_LINKS);
+    assert_eq!(links(&screen, 0), [(0, 8), (9, 17), (18, 26), (27, 35)]);
+}
+
+#[test]
+fn finds_no_url_without_one() {
+    let mut screen = grid(30, 4);
+    screen.print("no links here, nor http://\r\n");
+    assert_eq!(links(&screen, 0), []);
+    // Rows past the bottom have no links either
+    assert_eq!(links(&screen, 4), []);
+}
+
+#[test]
+fn finds_words_of_alphanumerics_and_path_punctuation() {
+    let mut screen = grid(30, 4);
+    screen.print("cd ~/src/my-app_v2.0 && ls");
+    assert_eq!(screen.word_at(10, 0), Some((4, 20)));
+    assert_eq!(screen.word_at(19, 0), Some((4, 20)));
+    assert_eq!(screen.word_at(3, 0), None);
+    assert_eq!(screen.word_at(21, 0), None);
+}
+
+#[test]
+fn finds_words_with_custom_word_chars() {
+    let mut screen = grid(30, 4);
+    screen.print("cd ~/src/my-app_v2.0 && ls");
+    screen.set_word_chars("~&");
+    assert_eq!(screen.word_at(3, 0), Some((3, 4)));
+    assert_eq!(screen.word_at(6, 0), Some((5, 8)));
+    assert_eq!(screen.word_at(10, 0), Some((9, 11)));
+    assert_eq!(screen.word_at(22, 0), Some((21, 23)));
+}
+
+#[test]
+fn finds_no_word_on_whitespace() {
+    let mut screen = grid(30, 4);
+    screen.print("a  b");
+    assert_eq!(screen.word_at(1, 0), None);
+    assert_eq!(screen.word_at(2, 0), None);
+    assert_eq!(screen.word_at(10, 1), None);
+}
+
+#[test]
+fn finds_words_touching_the_edges() {
+    let mut screen = grid(30, 4);
+    screen.print("start\x1b[2;28Hend");
+    assert_eq!(screen.word_at(0, 0), Some((0, 5)));
+    assert_eq!(screen.word_at(4, 0), Some((0, 5)));
+    assert_eq!(screen.word_at(27, 1), Some((27, 30)));
+    assert_eq!(screen.word_at(29, 1), Some((27, 30)));
+}
+
+#[test]
+fn finds_no_word_out_of_range() {
+    let mut screen = grid(30, 4);
+    screen.print(&"x".repeat(30 * 4));
+    assert_eq!(screen.word_at(30, 0), None);
+    assert_eq!(screen.word_at(0, 4), None);
+    assert_eq!(screen.word_at(usize::MAX, usize::MAX), None);
+}
+
+#[test]
+fn bell_inverts_the_margins_and_cursor_for_a_frame() {
+    let mut screen = grid(10, 4);
+    let mut panel = Panel::new();
+    screen.update_display(&mut panel);
+    let (width, _) = screen.screen_size();
+    let margin = (width as usize - 1, 0);
+    let palette = Palette::DEFAULT;
+    let frame = |panel: &Panel| (panel.pixel(margin.0, margin.1), panel.pixel(0, 0));
+    assert_eq!(frame(&panel), (palette.bg, palette.cursor));
+
+    screen.print("\x07");
+    screen.update_display(&mut panel);
+    assert_eq!(frame(&panel), (inverse(palette.bg), inverse(palette.cursor)));
+
+    screen.update_display(&mut panel);
+    assert_eq!(frame(&panel), (palette.bg, palette.cursor));
+}
+
+#[test]
+fn soft_reset_restores_modes_and_keeps_the_text() {
+    let mut screen = grid(10, 6);
+    for i in 0..8 {
+        screen.print(&format!("line {i}\r\n"));
+    }
+    screen.print("\x1b[1;31m\x1b[2;4r\x1b[?6h\x1b[?7l\x1b[2;3H");
+    let pen = screen.current_attrs;
+    let at = cursor(&screen);
+    let text = screen.to_text();
+    let scrollback = screen.scrollback.len();
+
+    // Only `!p` is DECSTR
+    screen.print("\x1b[!q\x1b[!m\x1b[!P");
+    assert_eq!(screen.current_attrs, pen);
+    assert_eq!((screen.scroll_top, screen.scroll_bottom), (1, 3));
+    assert!(screen.origin_mode);
+    assert!(!screen.autowrap);
+    assert_eq!(cursor(&screen), at);
+
+    screen.print("\x1b[!p");
+    assert_eq!(screen.current_attrs, Attrs::default());
+    assert_eq!((screen.scroll_top, screen.scroll_bottom), (0, 5));
+    assert!(!screen.origin_mode);
+    assert!(screen.autowrap);
+    assert_eq!(cursor(&screen), (0, 0));
+    assert_eq!(screen.to_text(), text);
+    assert_eq!(screen.scrollback.len(), scrollback);
+}
+
+#[test]
+fn accepts_only_right_angle_rotations() {
+    for degrees in [0, 90, 180, 270] {
+        assert_eq!(Rotation::from_degrees(degrees).map(Rotation::degrees), Some(degrees));
+    }
+    assert_eq!(Rotation::from_degrees(45), None);
+}